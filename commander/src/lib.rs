@@ -1,11 +1,17 @@
+#[cfg(feature = "serde")]
+mod serde_support;
+
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use new_string_template::template::Template;
 use std::collections::HashMap;
 use std::ffi::OsStr;
-use std::io::{Error, ErrorKind, Result, Write};
+use std::io::{Error, Result, Write};
 use std::process::{Command, Output};
 use std::time::Duration;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 pub struct CommandToExecute {
     name: String,
     command: Command,
@@ -64,35 +70,55 @@ impl CommandToExecute {
         self.verbose
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "command",
+            skip_all,
+            fields(
+                name = self.name.as_str(),
+                program = ?self.command.get_program(),
+                args = ?self.command.get_args().collect::<Vec<_>>()
+            )
+        )
+    )]
     pub fn execute(&mut self) -> Result<Output> {
+        #[cfg(feature = "tracing")]
+        let started = std::time::Instant::now();
+
         let output = match self.command.output() {
             Ok(output) => output,
             Err(error) => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!(
-                        "Failed to execute command `{}` due to `{}`:\n  {:?}",
-                        self.name(),
-                        error,
-                        &self.command,
-                    ),
-                ))
+                #[cfg(feature = "tracing")]
+                tracing::error!(error = %error, "Failed to execute the command");
+
+                return Err(Error::other(format!(
+                    "Failed to execute command `{}` due to `{}`:\n  {:?}",
+                    self.name(),
+                    error,
+                    &self.command,
+                )));
             }
         };
 
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            duration_ms = started.elapsed().as_millis() as u64,
+            exit_code = output.status.code(),
+            success = output.status.success(),
+            "Executed the command"
+        );
+
         if !output.status.success() {
             let stderr = String::from_utf8(output.stderr).unwrap();
 
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!(
-                    "Command {} didn't finish successfully (exit code = {:?}): {:?}\n{}",
-                    self.name(),
-                    output.status.code(),
-                    &self.command,
-                    stderr
-                ),
-            ));
+            return Err(Error::other(format!(
+                "Command {} didn't finish successfully (exit code = {:?}): {:?}\n{}",
+                self.name(),
+                output.status.code(),
+                &self.command,
+                stderr
+            )));
         }
         Ok(output)
     }
@@ -102,6 +128,8 @@ impl CommandToExecute {
     }
 }
 
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CommandsToExecute {
     commands: Vec<CommandToExecute>,
 }
@@ -111,6 +139,7 @@ impl CommandsToExecute {
         Self { commands: vec![] }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn add(mut self, command: CommandToExecute) -> Self {
         self.commands.push(command);
         self
@@ -150,8 +179,8 @@ impl CommandsToExecute {
                             .template("{prefix:.bold.dim} {spinner:.blue} {wide_msg}")
                             .unwrap(),
                     );
-                    pb.set_message(format!("{}", &message));
-                    pb.set_prefix(format!("{}", &prefix));
+                    pb.set_message(message.clone());
+                    pb.set_prefix(prefix.clone());
                     Some(pb)
                 }
             };
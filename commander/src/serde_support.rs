@@ -0,0 +1,85 @@
+use crate::CommandToExecute;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The serializable description of a command: `std::process::Command` itself can not be serialized,
+/// so we capture what it was configured with and build a new one when deserializing.
+#[derive(Serialize, Deserialize)]
+struct SerializedCommand {
+    #[serde(default)]
+    name: String,
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Environment variables to set, `None` means the variable is removed
+    #[serde(default)]
+    env: BTreeMap<String, Option<String>>,
+    #[serde(default)]
+    cwd: Option<PathBuf>,
+    #[serde(default)]
+    verbose: bool,
+    #[serde(default)]
+    log_prefix: Option<String>,
+    #[serde(default)]
+    log_message: Option<String>,
+}
+
+impl Serialize for CommandToExecute {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedCommand {
+            name: self.name.clone(),
+            program: self.command.get_program().to_string_lossy().to_string(),
+            args: self
+                .command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect(),
+            env: self
+                .command
+                .get_envs()
+                .map(|(key, value)| {
+                    (
+                        key.to_string_lossy().to_string(),
+                        value.map(|value| value.to_string_lossy().to_string()),
+                    )
+                })
+                .collect(),
+            cwd: self.command.get_current_dir().map(|cwd| cwd.to_path_buf()),
+            verbose: self.verbose,
+            log_prefix: Some(self.log_prefix.clone()),
+            log_message: Some(self.log_message.clone()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CommandToExecute {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = SerializedCommand::deserialize(deserializer)?;
+
+        let mut command = Command::new(serialized.program);
+        command.args(serialized.args);
+        for (key, value) in serialized.env {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        if let Some(cwd) = serialized.cwd {
+            command.current_dir(cwd);
+        }
+
+        let mut command_to_execute = CommandToExecute::new(command)
+            .with_name(serialized.name)
+            .with_verbose(serialized.verbose);
+        if let Some(log_prefix) = serialized.log_prefix {
+            command_to_execute = command_to_execute.with_log_prefix(log_prefix);
+        }
+        if let Some(log_message) = serialized.log_message {
+            command_to_execute.log_message = log_message;
+        }
+        Ok(command_to_execute)
+    }
+}
@@ -0,0 +1,33 @@
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    use commander::{CommandToExecute, CommandsToExecute};
+
+    let commands = CommandsToExecute::default().add(
+        CommandToExecute::build("cargo", |command| {
+            command
+                .arg("build")
+                .arg("--release")
+                .env("RUSTFLAGS", "-C target-cpu=native")
+                .env_remove("CARGO_TARGET_DIR")
+                .current_dir("/tmp");
+        })
+        .with_name("Build")
+        .with_verbose(true)
+        .without_log_prefix(),
+    );
+
+    let json = serde_json::to_string(&commands)?;
+    let deserialized: CommandsToExecute = serde_json::from_str(&json)?;
+    assert_eq!(serde_json::to_string(&deserialized)?, json);
+
+    let command: CommandToExecute =
+        serde_json::from_str(r#"{ "name": "Test", "program": "cargo", "args": ["test"] }"#)?;
+    assert_eq!(command.name(), "Test");
+    assert!(!command.is_verbose());
+    assert_eq!(
+        serde_json::to_value(&command)?["log_prefix"],
+        "[{ index }/{ total }]"
+    );
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use async_compression::tokio::write::{BzDecoder, GzipDecoder, XzDecoder, ZstdDecoder};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Compression of a downloaded file that is decompressed while streaming
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression from the extension of a file name or url
+    pub fn from_extension(name: &str) -> Option<Self> {
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Self::Gzip),
+            "xz" => Some(Self::Xz),
+            "bz2" | "bzip2" => Some(Self::Bzip2),
+            "zst" | "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Removes the extension of this compression from the file name, if present
+    pub(crate) fn strip_extension(&self, name: &str) -> String {
+        match name.rsplit_once('.') {
+            Some((stem, _)) if Self::from_extension(name) == Some(*self) && !stem.is_empty() => {
+                stem.to_string()
+            }
+            _ => name.to_string(),
+        }
+    }
+}
+
+/// Which bytes of a decompressed download the checksum is computed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ChecksumOf {
+    /// The bytes as transferred over the network
+    #[default]
+    Compressed,
+    /// The bytes written to the destination file
+    Decompressed,
+}
+
+/// Passes the written bytes to the inner writer, computing their checksum on the way if needed
+pub(crate) struct HashingWriter<W> {
+    inner: W,
+    hasher: Option<Sha256>,
+}
+
+impl<W> HashingWriter<W> {
+    pub(crate) fn new(inner: W, hasher: Option<Sha256>) -> Self {
+        Self { inner, hasher }
+    }
+
+    pub(crate) fn sha256(self) -> Option<String> {
+        self.hasher.map(|hasher| hex::encode(hasher.finalize()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for HashingWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(written)), Some(hasher)) = (&poll, this.hasher.as_mut()) {
+            hasher.update(&buf[..*written]);
+        }
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Decompresses the written bytes, if needed, before passing them to the inner writer
+pub(crate) enum Decompressor<W: AsyncWrite> {
+    None(W),
+    Gzip(GzipDecoder<W>),
+    Xz(XzDecoder<W>),
+    Bzip2(BzDecoder<W>),
+    Zstd(ZstdDecoder<W>),
+}
+
+impl<W: AsyncWrite> Decompressor<W> {
+    pub(crate) fn new(inner: W, compression: Option<Compression>) -> Self {
+        match compression {
+            None => Self::None(inner),
+            Some(Compression::Gzip) => Self::Gzip(GzipDecoder::new(inner)),
+            Some(Compression::Xz) => Self::Xz(XzDecoder::new(inner)),
+            Some(Compression::Bzip2) => Self::Bzip2(BzDecoder::new(inner)),
+            Some(Compression::Zstd) => Self::Zstd(ZstdDecoder::new(inner)),
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        match self {
+            Self::None(inner) => inner,
+            Self::Gzip(decoder) => decoder.into_inner(),
+            Self::Xz(decoder) => decoder.into_inner(),
+            Self::Bzip2(decoder) => decoder.into_inner(),
+            Self::Zstd(decoder) => decoder.into_inner(),
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Decompressor<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::None(inner) => Pin::new(inner).poll_write(cx, buf),
+            Self::Gzip(decoder) => Pin::new(decoder).poll_write(cx, buf),
+            Self::Xz(decoder) => Pin::new(decoder).poll_write(cx, buf),
+            Self::Bzip2(decoder) => Pin::new(decoder).poll_write(cx, buf),
+            Self::Zstd(decoder) => Pin::new(decoder).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::None(inner) => Pin::new(inner).poll_flush(cx),
+            Self::Gzip(decoder) => Pin::new(decoder).poll_flush(cx),
+            Self::Xz(decoder) => Pin::new(decoder).poll_flush(cx),
+            Self::Bzip2(decoder) => Pin::new(decoder).poll_flush(cx),
+            Self::Zstd(decoder) => Pin::new(decoder).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::None(inner) => Pin::new(inner).poll_shutdown(cx),
+            Self::Gzip(decoder) => Pin::new(decoder).poll_shutdown(cx),
+            Self::Xz(decoder) => Pin::new(decoder).poll_shutdown(cx),
+            Self::Bzip2(decoder) => Pin::new(decoder).poll_shutdown(cx),
+            Self::Zstd(decoder) => Pin::new(decoder).poll_shutdown(cx),
+        }
+    }
+}
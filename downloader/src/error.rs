@@ -1,4 +1,8 @@
+use crate::{DownloadReport, FileToDownload};
 use reqwest::{StatusCode, Url};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 use tokio::task::JoinError;
 
@@ -16,6 +20,146 @@ pub enum DownloaderError {
     JoinError(#[from] JoinError),
     #[error("Failed to download {0}, status code {1}")]
     DownloadError(Url, StatusCode),
+    #[error("Both {url} and {conflicting_url} would be downloaded to {path}")]
+    ConflictingDestination {
+        path: PathBuf,
+        url: String,
+        conflicting_url: String,
+    },
+    #[error("Both {sha256} and {conflicting_sha256} checksums are expected for {path}")]
+    ConflictingChecksum {
+        path: PathBuf,
+        sha256: String,
+        conflicting_sha256: String,
+    },
+    #[error("Timed out after {1:?} waiting for the lock {0}")]
+    LockTimeout(PathBuf, Duration),
+    #[error("Not enough free space in {directory}: {required} bytes required, {available} bytes available")]
+    InsufficientSpace {
+        directory: PathBuf,
+        required: u64,
+        available: u64,
+    },
+    #[error("Checksum of {path} is {actual}, expected {expected}")]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    #[error("Invalid OCI reference {0}, expected oci://registry/repository:tag")]
+    InvalidOciReference(String),
+    #[error("Could not find a layer {} in {reference}, available layers: {}", .title.as_deref().unwrap_or("to download"), .available.join(", "))]
+    OciLayerNotFound {
+        reference: String,
+        title: Option<String>,
+        available: Vec<String>,
+    },
+    #[error("Unsupported digest {0}, only sha256 is supported")]
+    UnsupportedDigest(String),
+    #[error("Unsupported authentication challenge {0}")]
+    UnsupportedAuthentication(String),
+    #[error("Failed to parse JSON")]
+    JsonError(#[from] serde_json::Error),
+    #[error("{url} is locked to the checksum {locked}, but {expected} is expected")]
+    LockfileMismatch {
+        url: String,
+        locked: String,
+        expected: String,
+    },
+    #[error("Failed to download {} of {} files", .0.failed().count(), .0.files().len())]
+    DownloadsFailed(Box<DownloadReport>),
+    #[error("Failed to download {} to {} while {phase}", .file.url(), .file.destination().display())]
+    FileError {
+        file: Box<FileToDownload>,
+        phase: DownloadPhase,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+impl DownloaderError {
+    /// The file which failed to download, if the error is specific to one file
+    pub fn file(&self) -> Option<&FileToDownload> {
+        match self {
+            Self::FileError { file, .. } => Some(file),
+            _ => None,
+        }
+    }
+
+    /// The message of the error followed by the messages of its causes
+    pub(crate) fn chain(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            message.push_str(": ");
+            message.push_str(&error.to_string());
+            source = error.source();
+        }
+        message
+    }
+
+    /// The phase of the download in which the error happened
+    pub fn phase(&self) -> Option<DownloadPhase> {
+        match self {
+            Self::FileError { phase, .. } => Some(*phase),
+            _ => None,
+        }
+    }
+}
+
+/// The steps of a [`crate::download_task`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadPhase {
+    /// Waiting for other processes downloading the same file
+    Lock,
+    /// Resolving where to download the file from, for example an OCI artifact layer
+    Resolve,
+    /// Requesting the size of the file
+    Probe,
+    /// Sending the download request
+    Connect,
+    /// Receiving the content of the file
+    Stream,
+    /// Writing the content to the disk
+    Write,
+    /// Verifying the checksum of the file
+    Verify,
+    /// Moving the complete download to its destination
+    Rename,
+    /// Applying permissions and running the post download hook
+    PostDownload,
+}
+
+impl Display for DownloadPhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let phase = match self {
+            Self::Lock => "waiting for the lock",
+            Self::Resolve => "resolving",
+            Self::Probe => "probing",
+            Self::Connect => "connecting",
+            Self::Stream => "streaming",
+            Self::Write => "writing",
+            Self::Verify => "verifying",
+            Self::Rename => "renaming",
+            Self::PostDownload => "running post download actions",
+        };
+        f.write_str(phase)
+    }
+}
+
+pub(crate) trait InPhase<T> {
+    /// Attaches the file and the phase of the download to the error
+    fn in_phase(self, file_to_download: &FileToDownload, phase: DownloadPhase) -> Result<T>;
+}
+
+impl<T, E: Into<DownloaderError>> InPhase<T> for core::result::Result<T, E> {
+    fn in_phase(self, file_to_download: &FileToDownload, phase: DownloadPhase) -> Result<T> {
+        self.map_err(|error| DownloaderError::FileError {
+            file: Box::new(file_to_download.clone()),
+            phase,
+            source: Box::new(error.into()),
+        })
+    }
 }
 
 impl<T> From<DownloaderError> for std::result::Result<T, DownloaderError> {
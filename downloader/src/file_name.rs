@@ -0,0 +1,75 @@
+use percent_encoding::percent_decode_str;
+use reqwest::header::{HeaderMap, CONTENT_DISPOSITION};
+use reqwest::Url;
+
+pub(crate) const DEFAULT_FILE_NAME: &str = "download";
+
+/// Infers the name of a downloaded file from the `Content-Disposition` header
+/// and falls back to the last segment of the (final) url.
+pub(crate) fn infer_file_name(url: &Url, headers: &HeaderMap) -> String {
+    headers
+        .get(CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(file_name_from_content_disposition)
+        .or_else(|| file_name_from_url(url))
+        .unwrap_or_else(|| DEFAULT_FILE_NAME.to_string())
+}
+
+pub(crate) fn file_name_from_url(url: &Url) -> Option<String> {
+    url.path_segments()
+        .and_then(|segments| segments.rev().find(|segment| !segment.is_empty()))
+        .map(|segment| percent_decode_str(segment).decode_utf8_lossy().to_string())
+        .and_then(|name| sanitize_file_name(&name))
+}
+
+fn file_name_from_content_disposition(content_disposition: &str) -> Option<String> {
+    let mut file_name = None;
+
+    for parameter in content_disposition.split(';').skip(1) {
+        let Some((key, value)) = parameter.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+
+        match key.trim().to_ascii_lowercase().as_str() {
+            // RFC 5987 extended value, e.g. filename*=UTF-8''na%C3%AFve.zip
+            // takes precedence over the plain filename
+            "filename*" => {
+                if let Some((_charset_and_language, encoded)) = value.rsplit_once('\'') {
+                    let decoded = percent_decode_str(encoded).decode_utf8_lossy().to_string();
+                    if let Some(name) = sanitize_file_name(&decoded) {
+                        return Some(name);
+                    }
+                }
+            }
+            "filename" => {
+                file_name = sanitize_file_name(value.trim_matches('"'));
+            }
+            _ => {}
+        }
+    }
+
+    file_name
+}
+
+/// Only keeps the last path component and removes characters that are not allowed in file names,
+/// so that a server can not make us write outside of the target directory.
+pub(crate) fn sanitize_file_name(name: &str) -> Option<String> {
+    let name = name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|character| {
+            !character.is_control() && !matches!(character, '<' | '>' | ':' | '"' | '|' | '?' | '*')
+        })
+        .collect::<String>();
+
+    let name = name.trim().trim_end_matches('.');
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
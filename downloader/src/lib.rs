@@ -1,58 +1,300 @@
+mod decompress;
 mod error;
+mod file_name;
+mod lock;
+mod lockfile;
+#[cfg(feature = "oci")]
+mod oci;
+mod post_download;
+mod report;
+mod space;
 
 use futures::{stream, StreamExt};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use reqwest::{header, Client, Url};
-use std::path::PathBuf;
+use reqwest::header::HeaderValue;
+use reqwest::{header, Client, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::task;
 
-pub use error::{DownloaderError, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub use decompress::{ChecksumOf, Compression};
+use decompress::{Decompressor, HashingWriter};
+use error::InPhase;
+pub use error::{DownloadPhase, DownloaderError, Result};
+use lock::DestinationLock;
+use lockfile::Lockfile;
+
+#[cfg(feature = "oci")]
+pub use oci::OciReference;
+pub use post_download::PostDownloadHook;
+pub use report::{DownloadReport, DownloadStatus, FileDownloadReport};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FileToDownload {
     url: String,
     directory: PathBuf,
-    file_name: String,
+    file_name: Option<String>,
+    sha256: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default = "default_lock_timeout"))]
+    lock_timeout: Duration,
+    unix_permissions: Option<u32>,
+    /// Hooks are code and can not be part of a serialized plan
+    #[cfg_attr(feature = "serde", serde(skip))]
+    post_download: Option<PostDownloadHook>,
+    oci_layer: Option<String>,
+    decompression: Option<Compression>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    checksum_of: ChecksumOf,
+}
+
+#[cfg(feature = "serde")]
+fn default_lock_timeout() -> Duration {
+    FileToDownload::DEFAULT_LOCK_TIMEOUT
 }
 
 impl FileToDownload {
+    /// How long to wait for another process downloading the same file by default
+    pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
     pub fn new(
         url: impl Into<String>,
         directory: impl Into<PathBuf>,
         file_name: impl Into<String>,
     ) -> Self {
+        Self {
+            file_name: Some(file_name.into()),
+            ..Self::from_url(url, directory)
+        }
+    }
+
+    /// Downloads a file into the directory, inferring its name from the `Content-Disposition`
+    /// header or from the last segment of the url after following redirects.
+    /// The url may also be a reference to an artifact in an OCI registry,
+    /// such as `oci://ghcr.io/feenkcom/vm:1.0`, the file is then named after the layer's title.
+    pub fn from_url(url: impl Into<String>, directory: impl Into<PathBuf>) -> Self {
         Self {
             directory: directory.into(),
             url: url.into(),
-            file_name: file_name.into(),
+            file_name: None,
+            sha256: None,
+            lock_timeout: Self::DEFAULT_LOCK_TIMEOUT,
+            unix_permissions: None,
+            post_download: None,
+            oci_layer: None,
+            decompression: None,
+            checksum_of: ChecksumOf::Compressed,
+        }
+    }
+
+    /// Verify the downloaded file against the expected hex encoded SHA-256 checksum
+    pub fn with_sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.sha256 = Some(sha256.into().to_ascii_lowercase());
+        self
+    }
+
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    /// How long to wait for another process that is downloading the same file.
+    /// Once that process finishes, its download is reused instead of downloading the file again.
+    pub fn with_lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    pub fn lock_timeout(&self) -> Duration {
+        self.lock_timeout
+    }
+
+    /// Set the permissions of the downloaded file, for example `0o755` to make it executable.
+    /// Ignored on platforms other than unix.
+    pub fn with_unix_permissions(mut self, mode: u32) -> Self {
+        self.unix_permissions = Some(mode);
+        self
+    }
+
+    pub fn unix_permissions(&self) -> Option<u32> {
+        self.unix_permissions
+    }
+
+    /// Run an action with the final path of the file before it is reported as finished.
+    /// The action also runs when the file was downloaded by another process,
+    /// so it should be safe to run it more than once.
+    pub fn with_post_download<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(PathBuf) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.post_download = Some(PostDownloadHook::new(hook));
+        self
+    }
+
+    /// Select the layer of an OCI artifact by its `org.opencontainers.image.title` annotation
+    pub fn with_oci_layer(mut self, title: impl Into<String>) -> Self {
+        self.oci_layer = Some(title.into());
+        self
+    }
+
+    pub fn oci_layer(&self) -> Option<&str> {
+        self.oci_layer.as_deref()
+    }
+
+    /// Decompress the file while downloading it, the destination file contains the decompressed bytes.
+    /// When the file name is inferred, the extension of the compression is removed from it.
+    pub fn with_decompression(mut self, compression: Compression) -> Self {
+        self.decompression = Some(compression);
+        self
+    }
+
+    pub fn decompression(&self) -> Option<Compression> {
+        self.decompression
+    }
+
+    /// Whether the expected checksum is of the compressed or decompressed bytes of a file
+    /// that is decompressed while downloading
+    pub fn with_checksum_of(mut self, checksum_of: ChecksumOf) -> Self {
+        self.checksum_of = checksum_of;
+        self
+    }
+
+    pub fn checksum_of(&self) -> ChecksumOf {
+        self.checksum_of
+    }
+
+    /// Is the checksum computed from the bytes of the destination file
+    fn is_checksum_of_file(&self) -> bool {
+        self.decompression.is_none() || self.checksum_of == ChecksumOf::Decompressed
+    }
+
+    fn inferred_file_name(&self, file_name: String) -> String {
+        match self.decompression {
+            Some(compression) => compression.strip_extension(&file_name),
+            None => file_name,
         }
     }
 
+    pub fn is_oci(&self) -> bool {
+        self.url.starts_with("oci://")
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    pub fn directory(&self) -> &Path {
+        self.directory.as_path()
+    }
+
+    /// Returns the name of the file to download. If the name should be inferred,
+    /// it is guessed from the url as the final name is only known once the download starts.
+    pub fn file_name(&self) -> String {
+        self.file_name.clone().unwrap_or_else(|| {
+            #[cfg(feature = "oci")]
+            if self.is_oci() {
+                if let Some(name) = self
+                    .oci_layer
+                    .as_deref()
+                    .and_then(file_name::sanitize_file_name)
+                    .or_else(|| {
+                        OciReference::parse(self.url.as_str())
+                            .ok()
+                            .and_then(|reference| file_name::sanitize_file_name(reference.name()))
+                    })
+                {
+                    return self.inferred_file_name(name);
+                }
+            }
+
+            Url::parse(self.url.as_str())
+                .ok()
+                .and_then(|url| file_name::file_name_from_url(&url))
+                .map(|name| self.inferred_file_name(name))
+                .unwrap_or_else(|| file_name::DEFAULT_FILE_NAME.to_string())
+        })
+    }
+
+    /// Returns true if the file exists. A file with an inferred name is found by the name
+    /// recorded when it was downloaded, rather than by the guessed name.
     pub fn already_downloaded(&self) -> bool {
-        self.path().exists()
+        let path = match self.file_name.as_ref() {
+            Some(_) => Some(self.path()),
+            None => lock::recorded_file_name(&self.lock_path())
+                .map(|file_name| self.directory.join(file_name)),
+        };
+        path.is_some_and(|path| path.exists())
     }
 
+    /// Returns the path of the file, see [`FileToDownload::file_name`]
     pub fn path(&self) -> PathBuf {
-        self.directory.join(&self.file_name)
+        self.directory.join(self.file_name())
+    }
+
+    /// Returns the path of the file only if its name is known, either given or resolved by the download
+    pub(crate) fn resolved_path(&self) -> Option<PathBuf> {
+        self.file_name
+            .as_ref()
+            .map(|file_name| self.directory.join(file_name))
+    }
+
+    /// Where the file is downloaded to, the directory if the name is not known yet
+    pub(crate) fn destination(&self) -> PathBuf {
+        self.resolved_path()
+            .unwrap_or_else(|| self.directory.clone())
+    }
+
+    fn with_resolved_file_name(&self, file_name: String) -> Self {
+        Self {
+            file_name: Some(file_name),
+            ..self.clone()
+        }
+    }
+
+    /// Downloads of the same file are serialized by a lock next to it.
+    /// The final name of an inferred file is only known once its download starts,
+    /// so such downloads are locked by their url instead.
+    fn lock_path(&self) -> PathBuf {
+        match self.file_name.as_ref() {
+            Some(file_name) => lock::lock_path(&self.directory.join(file_name)),
+            None => {
+                let url_sha256 = hex::encode(Sha256::digest(lockfile::key(self)));
+                self.directory.join(format!(".{}.lock", &url_sha256[..16]))
+            }
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FilesToDownload {
     files: Vec<FileToDownload>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    free_space_margin: u64,
+    lockfile: Option<PathBuf>,
 }
 
 impl FilesToDownload {
     pub fn new() -> Self {
-        Self { files: vec![] }
+        Self {
+            files: vec![],
+            free_space_margin: 0,
+            lockfile: None,
+        }
     }
 
-    pub fn add(self, file_to_download: FileToDownload) -> Self {
-        let mut files = self.files.clone();
-        files.push(file_to_download);
-        Self { files }
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, file_to_download: FileToDownload) -> Self {
+        self.files.push(file_to_download);
+        self
     }
 
     pub fn maybe_add(self, file_to_download: Option<FileToDownload>) -> Self {
@@ -63,20 +305,187 @@ impl FilesToDownload {
         }
     }
 
-    pub fn extend(self, files_to_download: Self) -> Self {
-        let mut files = self.files.clone();
-        files.extend(files_to_download.files);
-        Self { files }
+    pub fn extend(mut self, files_to_download: Self) -> Self {
+        self.files.extend(files_to_download.files);
+        self.free_space_margin = self
+            .free_space_margin
+            .max(files_to_download.free_space_margin);
+        self.lockfile = self.lockfile.or(files_to_download.lockfile);
+        self
+    }
+
+    /// Records the final url, size and checksum of every downloaded file in the lockfile.
+    /// Once recorded, the following downloads must match the checksum in the lockfile,
+    /// use [`FilesToDownload::update_lock`] to refresh the entries.
+    pub fn with_lockfile(mut self, lockfile: impl Into<PathBuf>) -> Self {
+        self.lockfile = Some(lockfile.into());
+        self
+    }
+
+    pub fn lockfile(&self) -> Option<&Path> {
+        self.lockfile.as_deref()
+    }
+
+    /// Before downloading, the free space of each destination filesystem is checked against
+    /// the size of the files. The margin is additionally required on every filesystem,
+    /// for example to be able to extract the downloaded archives.
+    /// Files decompressed while downloading are not counted as their final size is unknown,
+    /// the margin should leave room for them.
+    pub fn with_free_space_margin(mut self, bytes: u64) -> Self {
+        self.free_space_margin = bytes;
+        self
     }
 
     pub fn is_empty(&self) -> bool {
         self.files.is_empty()
     }
 
-    pub async fn download(self) -> Result<()> {
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Removes duplicated entries (same url and the same destination path)
+    /// and makes sure that no two different urls or checksums are downloaded into the same file.
+    /// Files with inferred names are only known to be the same when they have the same url
+    /// and directory, as their final names depend on the server.
+    pub fn validate(mut self) -> Result<Self> {
+        let mut destinations = HashMap::<PathBuf, usize>::new();
+        let mut files: Vec<FileToDownload> = Vec::with_capacity(self.files.len());
+
+        for file_to_download in std::mem::take(&mut self.files) {
+            let destination = file_to_download.lock_path();
+            let Some(index) = destinations.get(&destination) else {
+                destinations.insert(destination, files.len());
+                files.push(file_to_download);
+                continue;
+            };
+
+            let path = file_to_download.path();
+            let existing = &mut files[*index];
+            if existing.url != file_to_download.url {
+                return DownloaderError::ConflictingDestination {
+                    path,
+                    url: existing.url.clone(),
+                    conflicting_url: file_to_download.url,
+                }
+                .into();
+            }
+
+            match (&existing.sha256, file_to_download.sha256) {
+                (Some(sha256), Some(conflicting_sha256)) if sha256 != &conflicting_sha256 => {
+                    return DownloaderError::ConflictingChecksum {
+                        path,
+                        sha256: sha256.clone(),
+                        conflicting_sha256,
+                    }
+                    .into();
+                }
+                // keep the stricter of the two otherwise identical entries
+                (None, Some(sha256)) => existing.sha256 = Some(sha256),
+                _ => {}
+            }
+        }
+
+        self.files = files;
+        Ok(self)
+    }
+
+    /// Downloads the files and reports what happened to each of them.
+    /// A file that fails to download, for example because it no longer matches the lockfile,
+    /// is reported as failed without failing the others, so the report is returned even then.
+    /// Use [`DownloadReport::into_result`] to fail if any file failed.
+    pub async fn download(self) -> Result<DownloadReport> {
+        self.download_locked(false).await
+    }
+
+    /// Downloads the files without verifying them against the lockfile
+    /// and replaces their entries with the new downloads.
+    /// Files that already exist are downloaded again.
+    pub async fn update_lock(self) -> Result<DownloadReport> {
+        self.download_locked(true).await
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "download_files", skip_all, fields(files = self.len(), update_lock))
+    )]
+    async fn download_locked(self, update_lock: bool) -> Result<DownloadReport> {
         if self.is_empty() {
-            return Ok(());
+            return Ok(DownloadReport::default());
+        }
+
+        let mut files_to_download = self.validate()?;
+
+        // Files already in the lockfile must have the same checksum
+        let lockfile = match files_to_download.lockfile.as_ref() {
+            Some(path) => Some(Lockfile::load(path)?),
+            None => None,
+        };
+        if let (Some(lockfile), false) = (lockfile.as_ref(), update_lock) {
+            for file_to_download in files_to_download.files.iter_mut() {
+                let Some(locked_file) = lockfile.get(file_to_download) else {
+                    continue;
+                };
+                match file_to_download.sha256.as_ref() {
+                    Some(expected) if expected != &locked_file.sha256 => {
+                        return DownloaderError::LockfileMismatch {
+                            url: file_to_download.url.clone(),
+                            locked: locked_file.sha256.clone(),
+                            expected: expected.clone(),
+                        }
+                        .into();
+                    }
+                    _ => file_to_download.sha256 = Some(locked_file.sha256.clone()),
+                }
+            }
         }
+        let files = files_to_download.files.clone();
+
+        // Fail early if the files would not fit on the disk.
+        // Decompressed files take more space than their download and can not be
+        // sized upfront, they are covered by the margin. Files that exist or are
+        // being downloaded by another process take no more space.
+        // Only the counted files are resolved here, the others are resolved when downloaded.
+        let client = Client::new();
+        let sources = stream::iter(files_to_download.files.clone())
+            .map(|file_to_download| {
+                let client = client.clone();
+                async move {
+                    let counted = file_to_download.decompression.is_none()
+                        && (update_lock || !file_to_download.already_downloaded())
+                        && !lock::is_locked(&file_to_download.lock_path());
+                    if !counted {
+                        return None;
+                    }
+                    // the download reports the error if it persists
+                    let source = resolve_source(&client, &file_to_download).await;
+                    #[cfg(feature = "tracing")]
+                    if let Err(error) = &source {
+                        tracing::warn!(
+                            url = %file_to_download.url,
+                            error = %error.chain(),
+                            "Failed to resolve the size of the download"
+                        );
+                    }
+                    source.ok()
+                }
+            })
+            .buffered(4)
+            .collect::<Vec<Option<ResolvedSource>>>()
+            .await;
+
+        // The margin is required even if no file is counted
+        space::check_free_space(
+            files_to_download
+                .files
+                .iter()
+                .zip(sources.iter())
+                .map(|(file_to_download, source)| {
+                    let size = source.as_ref().and_then(|source| source.size).unwrap_or(0);
+                    (file_to_download.directory.clone(), size)
+                }),
+            files_to_download.free_space_margin,
+        )?;
 
         // Set up a new multi-progress bar.
         // The bar is stored in an `Arc` to facilitate sharing between threads.
@@ -86,7 +495,7 @@ impl FilesToDownload {
         let main_pb = Arc::new(
             multibar
                 .clone()
-                .add(ProgressBar::new(self.files.len() as u64)),
+                .add(ProgressBar::new(files_to_download.len() as u64)),
         );
 
         main_pb.set_style(
@@ -102,74 +511,176 @@ impl FilesToDownload {
 
         // Convert download_links Vector into stream
         // This is basically a async compatible iterator
-        let stream = stream::iter(&self.files);
+        let stream = stream::iter(files_to_download.files.into_iter().zip(sources));
 
         // Set up a future to iterate over tasks and run up to 2 at a time.
+        // The reports are collected in the same order as the files to download.
         let tasks = stream
-            .enumerate()
-            .for_each_concurrent(Some(2), |(_i, file_to_download)| {
+            .map(|(file_to_download, source)| {
                 // Clone multibar and main_pb.  We will move the clones into each task.
                 let multibar = multibar.clone();
                 let main_pb = main_pb.clone();
                 async move {
+                    let started = Instant::now();
+
                     // Spawn a new tokio task for the current download link
                     // We need to hand over the multibar, so the ProgressBar for the task can be added
-                    let _task =
-                        task::spawn(download_task(file_to_download.clone(), multibar)).await;
+                    let result = task::spawn(download_file(
+                        file_to_download.clone(),
+                        multibar,
+                        source,
+                        !update_lock,
+                    ))
+                    .await
+                    .map_err(DownloaderError::from)
+                    .and_then(|result| result);
 
                     // Increase main ProgressBar by 1
                     main_pb.inc(1);
-                }
-            });
 
-        // // Set up a future to manage rendering of the multiple progress bars.
-        // let multibar = {
-        //     // Create a clone of the multibar, which we will move into the task.
-        //     let multibar = multibar.clone();
-        //
-        //     // multibar.join() is *not* async and will block until all the progress
-        //     // bars are done, therefore we must spawn it on a separate scheduler
-        //     // on which blocking behavior is allowed.
-        //     task::spawn_blocking(move || multibar.)
-        // };
+                    result.unwrap_or_else(|error| {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(
+                            url = %file_to_download.url,
+                            status = "failed",
+                            error = %error.chain(),
+                            "Failed to download"
+                        );
+                        FileDownloadReport::failed(&file_to_download, started.elapsed(), &error)
+                    })
+                }
+            })
+            .buffered(2)
+            .collect::<Vec<FileDownloadReport>>();
 
         // Wait for the tasks to finish.
-        tasks.await;
+        let reports = tasks.await;
 
         // Change the message on the overall progress indicator.
         main_pb.finish_with_message("done");
         multibar.clear()?;
-        Ok(())
+
+        if let Some(path) = files_to_download.lockfile {
+            Lockfile::update(&path, |lockfile| {
+                let mut changed = false;
+                for (file_to_download, report) in files.iter().zip(reports.iter()) {
+                    if report.status() != DownloadStatus::Failed {
+                        changed |= lockfile.record(file_to_download, report);
+                    }
+                }
+                changed
+            })
+            .await?;
+        }
+
+        Ok(DownloadReport::new(reports))
     }
 }
 
 pub async fn download_task(
     file_to_download: FileToDownload,
     multibar: Arc<MultiProgress>,
-) -> Result<()> {
-    // Parse URL into Url type
-    let url = Url::parse(file_to_download.url.as_str())?;
+) -> Result<FileDownloadReport> {
+    download_file(file_to_download, multibar, None, true).await
+}
+
+/// Downloads a file, it is resolved first unless the source is already known.
+/// An existing file that matches the expected checksum is reused instead, unless `reuse_existing` is false.
+/// A partial file left behind by an interrupted download is resumed if the server supports ranges,
+/// see [`DownloadStatus::Resumed`].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "download",
+        skip_all,
+        fields(url = %file_to_download.url)
+    )
+)]
+async fn download_file(
+    file_to_download: FileToDownload,
+    multibar: Arc<MultiProgress>,
+    source: Option<ResolvedSource>,
+    reuse_existing: bool,
+) -> Result<FileDownloadReport> {
+    let started = Instant::now();
+
+    // Other processes may be downloading the same file into the same directory.
+    // The lock is held until the download task finishes.
+    let lock =
+        DestinationLock::acquire(&file_to_download.lock_path(), file_to_download.lock_timeout)
+            .await
+            .in_phase(&file_to_download, DownloadPhase::Lock)?;
+
+    // The file may have been downloaded by an earlier run,
+    // or by another process while we were waiting for the lock
+    let existing_file = match file_to_download.file_name.as_ref() {
+        Some(_) => Some(file_to_download.clone()),
+        None => lock
+            .file_name()
+            .map(|file_name| file_to_download.with_resolved_file_name(file_name)),
+    };
+    if let Some(existing_file) = existing_file.filter(|file| reuse_existing && file.path().exists())
+    {
+        if let Some(report) = reuse_download(&existing_file, started).await? {
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                status = "cached",
+                sha256 = report.sha256(),
+                "Reused the existing file"
+            );
+            return Ok(report);
+        }
+    }
 
     // Create a reqwest Client
     let client = Client::new();
 
-    // We need to determine the file size before we download, so we can create a ProgressBar
-    // A Header request for the CONTENT_LENGTH header gets us the file size
-    let download_size = {
-        let resp = client.head(url.as_str()).send().await?;
-        if resp.status().is_success() {
-            resp.headers() // Gives us the HeaderMap
-                .get(header::CONTENT_LENGTH) // Gives us an Option containing the HeaderValue
-                .and_then(|ct_len| ct_len.to_str().ok()) // Unwraps the Option as &str
-                .and_then(|ct_len| ct_len.parse().ok()) // Parses the Option as u64
-                .unwrap_or(0) // Fallback to 0
-        } else {
-            return DownloaderError::DownloadError(url, resp.status()).into();
-        }
+    // Find out where to download the file from, and its size, so we can create a ProgressBar
+    let source = match source {
+        Some(source) => source,
+        None => resolve_source(&client, &file_to_download).await?,
     };
+    let url = source.url.clone();
+    let download_size = source.size.unwrap_or(0);
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(source = %url, size = source.size, "Resolved the download");
 
     // Here we build the actual Request with a RequestBuilder from the Client
-    let request = client.get(url.as_str());
+    let request = |resume_from: Option<u64>| {
+        let mut request = client.get(url.as_str());
+        if let Some(authorization) = source.authorization.clone() {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        if let Some(resume_from) = resume_from {
+            request = request.header(header::RANGE, format!("bytes={resume_from}-"));
+        }
+        request
+    };
+
+    // A partial file left behind by an interrupted download is continued, if its name
+    // is known before the response and the bytes can be appended as they are.
+    // Only downloads verified against a checksum are resumed, so that a file that changed
+    // upstream in the meantime can not be silently mixed with the partial one.
+    let resume_from = match file_to_download
+        .file_name
+        .as_ref()
+        .or(source.file_name.as_ref())
+    {
+        Some(file_name)
+            if reuse_existing
+                && file_to_download.decompression.is_none()
+                && (file_to_download.sha256.is_some() || source.sha256.is_some()) =>
+        {
+            let partial_path = lock::partial_path(&file_to_download.directory.join(file_name));
+            tokio::fs::metadata(&partial_path)
+                .await
+                .ok()
+                .map(|metadata| metadata.len())
+                .filter(|size| *size > 0)
+        }
+        _ => None,
+    };
 
     // Create the ProgressBar with the aquired size from before
     // and add it to the multibar
@@ -183,37 +694,358 @@ pub async fn download_task(
             .progress_chars("#>-"),
     );
 
+    // Do the actual request to download the file
+    let mut download = request(resume_from)
+        .send()
+        .await
+        .in_phase(&file_to_download, DownloadPhase::Connect)?;
+    if resume_from.is_some() && download.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // the partial file does not fit the file on the server, start again
+        download = request(None)
+            .send()
+            .await
+            .in_phase(&file_to_download, DownloadPhase::Connect)?;
+    }
+    // Servers that do not support ranges send the whole file
+    let resumed_from = resume_from.filter(|_| download.status() == StatusCode::PARTIAL_CONTENT);
+    if !download.status().is_success() {
+        return Err(DownloaderError::DownloadError(url, download.status()))
+            .in_phase(&file_to_download, DownloadPhase::Connect);
+    }
+
+    // The name of the file may depend on the response, for example on the redirects
+    // or the `Content-Disposition` header
+    let file_name = match file_to_download
+        .file_name
+        .clone()
+        .or_else(|| source.file_name.clone())
+    {
+        Some(file_name) => file_name,
+        None => file_to_download.inferred_file_name(file_name::infer_file_name(
+            download.url(),
+            download.headers(),
+        )),
+    };
+
     // Set the filename as message part of the progress bar
-    progress_bar.set_message(file_to_download.file_name.clone());
+    progress_bar.set_message(file_name.clone());
+
+    // From now on errors refer to the resolved file
+    let file_to_download = file_to_download.with_resolved_file_name(file_name.clone());
 
     // Make sure that the target dir exists
     if !file_to_download.directory.exists() {
-        std::fs::create_dir_all(file_to_download.directory.as_path())?;
+        std::fs::create_dir_all(file_to_download.directory.as_path())
+            .in_phase(&file_to_download, DownloadPhase::Write)?;
     }
 
-    // Create the output file with tokio's async fs lib
-    let mut outfile =
-        tokio::fs::File::create(file_to_download.directory.join(&file_to_download.file_name))
-            .await?;
+    // Create the output file with tokio's async fs lib.
+    // The file is written next to the destination and is only renamed once complete,
+    // so that a present destination file is always a finished download.
+    let path = file_to_download.path();
+    let partial_path = lock::partial_path(&path);
+    let final_url = download.url().to_string();
 
-    // Do the actual request to download the file
-    let mut download = request.send().await?;
+    let written = async {
+        // The downloaded bytes are hashed unless only the checksum of the decompressed bytes
+        // is needed, those are hashed while written. Without decompression both are the same.
+        let decompressed_checksum = file_to_download.decompression.is_some()
+            && file_to_download.checksum_of == ChecksumOf::Decompressed;
+        let downloaded_checksum = !decompressed_checksum || source.sha256.is_some();
+
+        let (outfile, mut hasher) = match resumed_from {
+            Some(resumed_from) => {
+                // the checksum covers the bytes downloaded before the interruption
+                let hasher = hasher_of_file(&partial_path)
+                    .await
+                    .in_phase(&file_to_download, DownloadPhase::Write)?;
+                let outfile = tokio::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&partial_path)
+                    .await
+                    .in_phase(&file_to_download, DownloadPhase::Write)?;
+                progress_bar.set_position(resumed_from);
+                (outfile, Some(hasher))
+            }
+            None => {
+                let outfile = tokio::fs::File::create(&partial_path)
+                    .await
+                    .in_phase(&file_to_download, DownloadPhase::Write)?;
+                (outfile, downloaded_checksum.then(Sha256::new))
+            }
+        };
+
+        // Compressed downloads are decompressed on the fly
+        let mut outfile = Decompressor::new(
+            HashingWriter::new(outfile, decompressed_checksum.then(Sha256::new)),
+            file_to_download.decompression,
+        );
+        let mut bytes = 0u64;
+
+        // Do an asynchronous, buffered copy of the download to the output file.
+        //
+        // We use the part from the reqwest-tokio example here on purpose
+        // This way, we are able to increase the ProgressBar with every downloaded chunk
+        while let Some(chunk) = download
+            .chunk()
+            .await
+            .in_phase(&file_to_download, DownloadPhase::Stream)?
+        {
+            progress_bar.inc(chunk.len() as u64); // Increase ProgressBar by chunk size
+            bytes += chunk.len() as u64;
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(&chunk);
+            }
+            // Write chunk to output file
+            outfile
+                .write_all(&chunk)
+                .await
+                .in_phase(&file_to_download, DownloadPhase::Write)?;
+        }
+
+        // Finish the progress bar to prevent glitches
+        progress_bar.finish();
+
+        // Must flush tokio::io::BufWriter manually.
+        // It will *not* flush itself automatically when dropped.
+        // Shutting down also lets the decompressor write out the rest of the file.
+        outfile
+            .shutdown()
+            .await
+            .in_phase(&file_to_download, DownloadPhase::Write)?;
 
-    // Do an asynchronous, buffered copy of the download to the output file.
-    //
-    // We use the part from the reqwest-tokio example here on purpose
-    // This way, we are able to increase the ProgressBar with every downloaded chunk
-    while let Some(chunk) = download.chunk().await? {
-        progress_bar.inc(chunk.len() as u64); // Increase ProgressBar by chunk size
-        outfile.write(&chunk).await?; // Write chunk to output file
+        let downloaded_sha256 = hasher.map(|hasher| hex::encode(hasher.finalize()));
+        let written_sha256 = outfile.into_inner().sha256();
+        if let Some(downloaded_sha256) = downloaded_sha256.as_ref() {
+            source
+                .verify_sha256(&path, downloaded_sha256)
+                .in_phase(&file_to_download, DownloadPhase::Verify)?;
+        }
+        let sha256 = written_sha256.or(downloaded_sha256).unwrap_or_default();
+        verify_sha256(&file_to_download, &path, &sha256)
+            .in_phase(&file_to_download, DownloadPhase::Verify)?;
+
+        // The file must have its permissions as soon as it appears under its final name
+        set_unix_permissions(&file_to_download, &partial_path)
+            .await
+            .in_phase(&file_to_download, DownloadPhase::PostDownload)?;
+        tokio::fs::rename(&partial_path, &path)
+            .await
+            .in_phase(&file_to_download, DownloadPhase::Rename)?;
+        Ok::<_, DownloaderError>((bytes, sha256))
     }
+    .await;
+
+    // Do not leave a corrupted or incomplete file behind,
+    // the original error matters more than a failed clean up
+    let (bytes, sha256) = match written {
+        Ok(written) => written,
+        Err(error) => {
+            let _ = tokio::fs::remove_file(&partial_path).await;
+            return Err(error);
+        }
+    };
 
-    // Finish the progress bar to prevent glitches
-    progress_bar.finish();
+    lock.record_file_name(&file_name)
+        .in_phase(&file_to_download, DownloadPhase::Rename)?;
 
-    // Must flush tokio::io::BufWriter manually.
-    // It will *not* flush itself automatically when dropped.
-    outfile.flush().await?;
+    post_download(&file_to_download, &path)
+        .await
+        .in_phase(&file_to_download, DownloadPhase::PostDownload)?;
 
+    let report = FileDownloadReport::downloaded(
+        &file_to_download,
+        path,
+        bytes,
+        started.elapsed(),
+        final_url,
+        sha256,
+        resumed_from,
+    );
+
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        status = if resumed_from.is_some() {
+            "resumed"
+        } else {
+            "downloaded"
+        },
+        resumed_from,
+        bytes = report.bytes(),
+        duration_ms = report.duration().as_millis() as u64,
+        bytes_per_second = report.bytes_per_second(),
+        final_url = report.final_url(),
+        sha256 = report.sha256(),
+        "Downloaded"
+    );
+
+    Ok(report)
+}
+
+/// Reports the existing file as cached, or returns None if it does not match the expected checksum
+/// and must be downloaded again
+async fn reuse_download(
+    file_to_download: &FileToDownload,
+    started: Instant,
+) -> Result<Option<FileDownloadReport>> {
+    let path = file_to_download.path();
+
+    // The checksum of the compressed bytes can not be verified once the file is decompressed
+    let sha256 = if file_to_download.is_checksum_of_file() {
+        let sha256 = sha256_of_file(&path)
+            .await
+            .in_phase(file_to_download, DownloadPhase::Verify)?;
+        if verify_sha256(file_to_download, &path, &sha256).is_err() {
+            return Ok(None);
+        }
+        Some(sha256)
+    } else {
+        None
+    };
+    set_unix_permissions(file_to_download, &path)
+        .await
+        .in_phase(file_to_download, DownloadPhase::PostDownload)?;
+    post_download(file_to_download, &path)
+        .await
+        .in_phase(file_to_download, DownloadPhase::PostDownload)?;
+
+    Ok(Some(FileDownloadReport::cached(
+        file_to_download,
+        path,
+        started.elapsed(),
+        sha256,
+    )))
+}
+
+async fn set_unix_permissions(file_to_download: &FileToDownload, path: &Path) -> Result<()> {
+    if let Some(mode) = file_to_download.unix_permissions {
+        post_download::set_unix_permissions(path, mode).await?;
+    }
+    Ok(())
+}
+
+async fn post_download(file_to_download: &FileToDownload, path: &Path) -> Result<()> {
+    if let Some(hook) = file_to_download.post_download.as_ref() {
+        hook.run(path).await?;
+    }
     Ok(())
 }
+
+fn verify_sha256(file_to_download: &FileToDownload, path: &Path, sha256: &str) -> Result<()> {
+    match file_to_download.sha256.as_ref() {
+        Some(expected) if expected != sha256 => DownloaderError::ChecksumMismatch {
+            path: path.to_path_buf(),
+            expected: expected.clone(),
+            actual: sha256.to_string(),
+        }
+        .into(),
+        _ => Ok(()),
+    }
+}
+
+async fn sha256_of_file(path: &Path) -> Result<String> {
+    Ok(hex::encode(hasher_of_file(path).await?.finalize()))
+}
+
+/// Hashes the content of a file, more bytes can be added to the returned hasher
+async fn hasher_of_file(path: &Path) -> Result<Sha256> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher)
+}
+
+/// Where the content of a file is actually downloaded from
+#[derive(Debug, Clone)]
+struct ResolvedSource {
+    url: Url,
+    authorization: Option<HeaderValue>,
+    size: Option<u64>,
+    file_name: Option<String>,
+    /// The checksum the source promises, such as the digest of an OCI blob
+    sha256: Option<String>,
+}
+
+impl ResolvedSource {
+    fn verify_sha256(&self, path: &Path, sha256: &str) -> Result<()> {
+        match self.sha256.as_ref() {
+            Some(expected) if expected != sha256 => DownloaderError::ChecksumMismatch {
+                path: path.to_path_buf(),
+                expected: expected.clone(),
+                actual: sha256.to_string(),
+            }
+            .into(),
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn resolve_source(
+    client: &Client,
+    file_to_download: &FileToDownload,
+) -> Result<ResolvedSource> {
+    #[cfg(feature = "oci")]
+    if file_to_download.is_oci() {
+        let reference = OciReference::parse(file_to_download.url.as_str())
+            .in_phase(file_to_download, DownloadPhase::Resolve)?;
+        let blob = oci::resolve_blob(
+            client,
+            &reference,
+            file_to_download.oci_layer.as_deref(),
+            file_to_download.file_name.as_deref(),
+        )
+        .await
+        .in_phase(file_to_download, DownloadPhase::Resolve)?;
+
+        return Ok(ResolvedSource {
+            url: blob.url,
+            authorization: blob.authorization,
+            size: Some(blob.size),
+            file_name: blob
+                .title
+                .as_deref()
+                .and_then(file_name::sanitize_file_name)
+                .map(|file_name| file_to_download.inferred_file_name(file_name)),
+            sha256: Some(blob.sha256),
+        });
+    }
+
+    // Parse URL into Url type
+    let url = Url::parse(file_to_download.url.as_str())
+        .in_phase(file_to_download, DownloadPhase::Connect)?;
+
+    let size = probe_download_size(client, url.clone())
+        .await
+        .in_phase(file_to_download, DownloadPhase::Probe)?;
+
+    Ok(ResolvedSource {
+        url,
+        authorization: None,
+        size,
+        file_name: None,
+        sha256: None,
+    })
+}
+
+/// A Header request for the CONTENT_LENGTH header gets us the file size
+async fn probe_download_size(client: &Client, url: Url) -> Result<Option<u64>> {
+    let resp = client.head(url.as_str()).send().await?;
+    if resp.status().is_success() {
+        Ok(resp
+            .headers() // Gives us the HeaderMap
+            .get(header::CONTENT_LENGTH) // Gives us an Option containing the HeaderValue
+            .and_then(|ct_len| ct_len.to_str().ok()) // Unwraps the Option as &str
+            .and_then(|ct_len| ct_len.parse().ok())) // Parses the Option as u64
+    } else {
+        DownloaderError::DownloadError(url, resp.status()).into()
+    }
+}
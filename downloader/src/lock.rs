@@ -0,0 +1,98 @@
+use crate::{DownloaderError, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An advisory lock on a download destination shared between processes.
+/// The lock is released when dropped. The `.lock` file itself is left behind on purpose,
+/// removing it would race with other processes waiting for it.
+/// The `.lock` file contains the name of the last file downloaded under the lock,
+/// as it may differ from the name guessed before the download.
+#[derive(Debug)]
+pub(crate) struct DestinationLock {
+    file: File,
+}
+
+impl DestinationLock {
+    pub(crate) async fn acquire(lock_path: &Path, timeout: Duration) -> Result<Self> {
+        if let Some(directory) = lock_path.parent() {
+            tokio::fs::create_dir_all(directory).await?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(lock_path)?;
+
+        let started = Instant::now();
+        #[cfg(feature = "tracing")]
+        let mut waited = false;
+
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { file }),
+                Err(TryLockError::WouldBlock) => {
+                    if started.elapsed() >= timeout {
+                        return DownloaderError::LockTimeout(lock_path.to_path_buf(), timeout)
+                            .into();
+                    }
+                    #[cfg(feature = "tracing")]
+                    if !std::mem::replace(&mut waited, true) {
+                        tracing::info!(lock = %lock_path.display(), "Waiting for another process");
+                    }
+                    tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+                }
+                Err(TryLockError::Error(error)) => return Err(error.into()),
+            }
+        }
+    }
+
+    /// The name of the file recorded by the last holder of the lock
+    pub(crate) fn file_name(&self) -> Option<String> {
+        let mut content = String::new();
+        (&self.file).seek(SeekFrom::Start(0)).ok()?;
+        (&self.file).read_to_string(&mut content).ok()?;
+        parse_file_name(content)
+    }
+
+    pub(crate) fn record_file_name(&self, file_name: &str) -> std::io::Result<()> {
+        self.file.set_len(0)?;
+        (&self.file).seek(SeekFrom::Start(0))?;
+        (&self.file).write_all(file_name.as_bytes())
+    }
+}
+
+/// Returns true if another process currently holds the lock
+pub(crate) fn is_locked(lock_path: &Path) -> bool {
+    File::open(lock_path).is_ok_and(|file| matches!(file.try_lock(), Err(TryLockError::WouldBlock)))
+}
+
+/// Reads the name of the file recorded in the lock without acquiring it
+pub(crate) fn recorded_file_name(lock_path: &Path) -> Option<String> {
+    parse_file_name(std::fs::read_to_string(lock_path).ok()?)
+}
+
+fn parse_file_name(content: String) -> Option<String> {
+    let file_name = content.trim();
+    (!file_name.is_empty()).then(|| file_name.to_string())
+}
+
+pub(crate) fn lock_path(destination: &Path) -> PathBuf {
+    with_suffix(destination, "lock")
+}
+
+pub(crate) fn partial_path(destination: &Path) -> PathBuf {
+    with_suffix(destination, "part")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
@@ -0,0 +1,114 @@
+use crate::lock::{self, DestinationLock};
+use crate::{FileDownloadReport, FileToDownload, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const LOCKFILE_VERSION: u32 = 1;
+
+/// Pins the content of every downloaded url, so that a changed upstream artifact is detected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Lockfile {
+    version: u32,
+    files: BTreeMap<String, LockedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct LockedFile {
+    pub(crate) final_url: Option<String>,
+    pub(crate) size: u64,
+    pub(crate) sha256: String,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Self {
+            version: LOCKFILE_VERSION,
+            files: Default::default(),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Reads the lockfile, a missing lockfile is empty
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    /// Records changes in the lockfile. Other processes may update the same lockfile,
+    /// so it is reloaded under a lock and the changes are merged into it.
+    /// The lockfile is replaced atomically, only if `record` returns true.
+    pub(crate) async fn update(path: &Path, record: impl FnOnce(&mut Self) -> bool) -> Result<()> {
+        let _lock =
+            DestinationLock::acquire(&lock::lock_path(path), FileToDownload::DEFAULT_LOCK_TIMEOUT)
+                .await?;
+
+        let mut lockfile = Self::load(path)?;
+        if record(&mut lockfile) {
+            lockfile.save(path)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the lockfile next to its destination first, so that it is never read half written
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+
+        let partial_path = lock::partial_path(path);
+        std::fs::write(&partial_path, json)?;
+        std::fs::rename(&partial_path, path)?;
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, file_to_download: &FileToDownload) -> Option<&LockedFile> {
+        self.files.get(&key(file_to_download))
+    }
+
+    /// Records a successfully downloaded file, returns true if the lockfile changed
+    pub(crate) fn record(
+        &mut self,
+        file_to_download: &FileToDownload,
+        report: &FileDownloadReport,
+    ) -> bool {
+        let Some(sha256) = report.sha256() else {
+            return false;
+        };
+
+        let key = key(file_to_download);
+        let locked_file = LockedFile {
+            // files reused from another process do not know where they were downloaded from
+            final_url: report
+                .final_url()
+                .map(|final_url| final_url.to_string())
+                .or_else(|| {
+                    self.files
+                        .get(&key)
+                        .and_then(|locked_file| locked_file.final_url.clone())
+                }),
+            size: report
+                .path()
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len())
+                .unwrap_or_else(|| report.bytes()),
+            sha256: sha256.to_string(),
+        };
+
+        if self.files.get(&key) == Some(&locked_file) {
+            return false;
+        }
+        self.files.insert(key, locked_file);
+        true
+    }
+}
+
+/// Layers of the same OCI artifact share the url
+pub(crate) fn key(file_to_download: &FileToDownload) -> String {
+    match file_to_download.oci_layer() {
+        Some(layer) => format!("{}#{}", file_to_download.url(), layer),
+        None => file_to_download.url().to_string(),
+    }
+}
@@ -0,0 +1,313 @@
+use crate::{DownloaderError, Result};
+use reqwest::header::{HeaderValue, ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{Client, Response, StatusCode, Url};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+pub(crate) const OCI_SCHEME: &str = "oci://";
+
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.v2+json";
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+
+/// A reference to an artifact in an OCI registry such as `oci://ghcr.io/feenkcom/vm:1.0`
+/// or `oci://ghcr.io/feenkcom/vm@sha256:...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OciReference {
+    registry: String,
+    repository: String,
+    reference: String,
+}
+
+impl OciReference {
+    pub fn parse(reference: &str) -> Result<Self> {
+        let invalid = || DownloaderError::InvalidOciReference(reference.to_string());
+
+        let without_scheme = reference.strip_prefix(OCI_SCHEME).ok_or_else(invalid)?;
+        let (registry, name) = without_scheme.split_once('/').ok_or_else(invalid)?;
+
+        let (repository, reference) = match name.split_once('@') {
+            Some((repository, digest)) => (repository, digest),
+            None => match name.rsplit_once(':') {
+                // a colon before the last slash would be a port, not a tag
+                Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+                _ => (name, "latest"),
+            },
+        };
+
+        if registry.is_empty() || repository.is_empty() || reference.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            registry: registry.to_string(),
+            repository: repository.to_string(),
+            reference: reference.to_string(),
+        })
+    }
+
+    pub fn registry(&self) -> &str {
+        self.registry.as_str()
+    }
+
+    pub fn repository(&self) -> &str {
+        self.repository.as_str()
+    }
+
+    /// A tag or a digest of the manifest
+    pub fn reference(&self) -> &str {
+        self.reference.as_str()
+    }
+
+    /// The last component of the repository, used as a file name when it is not known yet
+    pub(crate) fn name(&self) -> &str {
+        self.repository
+            .rsplit('/')
+            .next()
+            .unwrap_or(self.repository.as_str())
+    }
+
+    /// Like container engines do, registries running on the local machine are accessed over plain http
+    fn scheme(&self) -> &'static str {
+        let host = self
+            .registry
+            .rsplit_once(':')
+            .filter(|(_, port)| port.chars().all(|c| c.is_ascii_digit()))
+            .map(|(host, _)| host)
+            .unwrap_or(self.registry.as_str());
+
+        match host {
+            "localhost" | "127.0.0.1" | "[::1]" => "http",
+            _ => "https",
+        }
+    }
+
+    fn repository_url(&self) -> String {
+        format!(
+            "{}://{}/v2/{}",
+            self.scheme(),
+            self.registry,
+            self.repository
+        )
+    }
+
+    fn manifest_url(&self) -> Result<Url> {
+        Ok(Url::parse(&format!(
+            "{}/manifests/{}",
+            self.repository_url(),
+            self.reference
+        ))?)
+    }
+
+    fn blob_url(&self, digest: &str) -> Result<Url> {
+        Ok(Url::parse(&format!(
+            "{}/blobs/{}",
+            self.repository_url(),
+            digest
+        ))?)
+    }
+}
+
+impl Display for OciReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let separator = if self.reference.contains(':') {
+            '@'
+        } else {
+            ':'
+        };
+        write!(
+            f,
+            "{}{}/{}{}{}",
+            OCI_SCHEME, self.registry, self.repository, separator, self.reference
+        )
+    }
+}
+
+/// A layer of an artifact resolved from its manifest
+#[derive(Debug, Clone)]
+pub(crate) struct OciBlob {
+    pub(crate) url: Url,
+    pub(crate) authorization: Option<HeaderValue>,
+    pub(crate) sha256: String,
+    pub(crate) size: u64,
+    pub(crate) title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    digest: String,
+    size: u64,
+    #[serde(default)]
+    annotations: HashMap<String, String>,
+}
+
+impl Descriptor {
+    fn title(&self) -> Option<&str> {
+        self.annotations
+            .get(TITLE_ANNOTATION)
+            .map(|title| title.as_str())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// Fetches the manifest of the artifact and selects the layer to download.
+/// The layer is selected by its title annotation. Without a title the only layer of the artifact
+/// is selected, or the one titled as the file to download.
+pub(crate) async fn resolve_blob(
+    client: &Client,
+    reference: &OciReference,
+    title: Option<&str>,
+    file_name: Option<&str>,
+) -> Result<OciBlob> {
+    let mut authorization = None;
+    let response = get_authorized(
+        client,
+        reference.manifest_url()?,
+        Some(MANIFEST_MEDIA_TYPES),
+        &mut authorization,
+    )
+    .await?;
+    let manifest: Manifest = serde_json::from_slice(&response.bytes().await?)?;
+
+    let layer = match title {
+        Some(title) => manifest
+            .layers
+            .iter()
+            .find(|layer| layer.title() == Some(title)),
+        None if manifest.layers.len() == 1 => manifest.layers.first(),
+        None => manifest
+            .layers
+            .iter()
+            .find(|layer| file_name.is_some() && layer.title() == file_name),
+    }
+    .ok_or_else(|| DownloaderError::OciLayerNotFound {
+        reference: reference.to_string(),
+        title: title.or(file_name).map(|title| title.to_string()),
+        available: manifest
+            .layers
+            .iter()
+            .map(|layer| layer.title().unwrap_or(layer.digest.as_str()).to_string())
+            .collect(),
+    })?;
+
+    let sha256 = layer
+        .digest
+        .strip_prefix("sha256:")
+        .ok_or_else(|| DownloaderError::UnsupportedDigest(layer.digest.clone()))?
+        .to_ascii_lowercase();
+
+    Ok(OciBlob {
+        url: reference.blob_url(&layer.digest)?,
+        authorization,
+        sha256,
+        size: layer.size,
+        title: layer.title().map(|title| title.to_string()),
+    })
+}
+
+/// Performs a GET request, going through the token authentication handshake
+/// if the registry requires it. The obtained authorization is kept for the following requests.
+async fn get_authorized(
+    client: &Client,
+    url: Url,
+    accept: Option<&str>,
+    authorization: &mut Option<HeaderValue>,
+) -> Result<Response> {
+    let request = |authorization: &Option<HeaderValue>| {
+        let mut request = client.get(url.clone());
+        if let Some(accept) = accept {
+            request = request.header(ACCEPT, accept);
+        }
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization.clone());
+        }
+        request
+    };
+
+    let mut response = request(authorization).send().await?;
+
+    if response.status() == StatusCode::UNAUTHORIZED && authorization.is_none() {
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|challenge| challenge.to_str().ok())
+            .map(|challenge| challenge.to_string());
+
+        if let Some(challenge) = challenge {
+            *authorization = Some(request_token(client, &challenge).await?);
+            response = request(authorization).send().await?;
+        }
+    }
+
+    if !response.status().is_success() {
+        return DownloaderError::DownloadError(url, response.status()).into();
+    }
+
+    Ok(response)
+}
+
+/// Requests an anonymous bearer token as described by the `WWW-Authenticate` challenge,
+/// for example `Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:feenkcom/vm:pull"`
+async fn request_token(client: &Client, challenge: &str) -> Result<HeaderValue> {
+    let unsupported = || DownloaderError::UnsupportedAuthentication(challenge.to_string());
+
+    let parameters =
+        challenge_parameters(challenge.strip_prefix("Bearer ").ok_or_else(unsupported)?);
+
+    let mut realm = Url::parse(parameters.get("realm").ok_or_else(unsupported)?)?;
+    for key in ["service", "scope"] {
+        if let Some(value) = parameters.get(key) {
+            realm.query_pairs_mut().append_pair(key, value);
+        }
+    }
+
+    let response = client.get(realm.clone()).send().await?;
+    if !response.status().is_success() {
+        return DownloaderError::DownloadError(realm, response.status()).into();
+    }
+
+    let token: TokenResponse = serde_json::from_slice(&response.bytes().await?)?;
+    let token = token.token.or(token.access_token).ok_or_else(unsupported)?;
+
+    HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| unsupported())
+}
+
+/// Splits `key="value",key=value` pairs, the quoted values may contain commas
+fn challenge_parameters(parameters: &str) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+    let mut rest = parameters.trim();
+
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let value = value.trim_start();
+
+        let (value, remaining) = match value.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, remaining)) => (value, remaining),
+                None => (quoted, ""),
+            },
+            None => match value.split_once(',') {
+                Some((value, remaining)) => (value.trim(), remaining),
+                None => (value.trim(), ""),
+            },
+        };
+
+        result.insert(key, value.to_string());
+        rest = remaining;
+    }
+
+    result
+}
@@ -0,0 +1,43 @@
+use crate::Result;
+use futures::future::BoxFuture;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An async action executed with the final path of a downloaded file
+#[derive(Clone)]
+pub struct PostDownloadHook(Arc<dyn Fn(PathBuf) -> BoxFuture<'static, Result<()>> + Send + Sync>);
+
+impl PostDownloadHook {
+    pub fn new<F, Fut>(hook: F) -> Self
+    where
+        F: Fn(PathBuf) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Self(Arc::new(move |path| Box::pin(hook(path))))
+    }
+
+    pub async fn run(&self, path: &Path) -> Result<()> {
+        (self.0)(path.to_path_buf()).await
+    }
+}
+
+impl Debug for PostDownloadHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostDownloadHook").finish_non_exhaustive()
+    }
+}
+
+#[cfg(unix)]
+pub(crate) async fn set_unix_permissions(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn set_unix_permissions(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
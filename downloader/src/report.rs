@@ -0,0 +1,210 @@
+use crate::{DownloaderError, FileToDownload, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Describes what happened to every file of a [`crate::FilesToDownload`] batch,
+/// it can be serialized to JSON for example to be archived by CI.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DownloadReport {
+    files: Vec<FileDownloadReport>,
+}
+
+impl DownloadReport {
+    pub(crate) fn new(files: Vec<FileDownloadReport>) -> Self {
+        Self { files }
+    }
+
+    pub fn files(&self) -> &[FileDownloadReport] {
+        self.files.as_slice()
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &FileDownloadReport> {
+        self.files
+            .iter()
+            .filter(|file| file.status() == DownloadStatus::Failed)
+    }
+
+    /// True if no file failed to download
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none()
+    }
+
+    /// Returns the report if no file failed to download,
+    /// otherwise a [`DownloaderError::DownloadsFailed`] error that carries the report
+    pub fn into_result(self) -> Result<Self> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            DownloaderError::DownloadsFailed(Box::new(self)).into()
+        }
+    }
+
+    /// Total amount of bytes transferred over the network
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.bytes()).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum DownloadStatus {
+    Downloaded,
+    /// A download that was interrupted before it could clean up, for example by a killed process,
+    /// was continued from the partial file it left behind.
+    /// Only downloads with a known checksum and without decompression are resumed
+    Resumed,
+    /// The file already existed and was not downloaded again,
+    /// for example because an earlier run or another process downloaded it
+    Cached,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDownloadReport {
+    url: String,
+    path: Option<PathBuf>,
+    status: DownloadStatus,
+    bytes: u64,
+    #[serde(serialize_with = "serialize_seconds")]
+    duration: Duration,
+    bytes_per_second: f64,
+    final_url: Option<String>,
+    sha256: Option<String>,
+    resumed_from: Option<u64>,
+    error: Option<String>,
+}
+
+impl FileDownloadReport {
+    pub(crate) fn downloaded(
+        file_to_download: &FileToDownload,
+        path: PathBuf,
+        bytes: u64,
+        duration: Duration,
+        final_url: String,
+        sha256: String,
+        resumed_from: Option<u64>,
+    ) -> Self {
+        Self {
+            url: file_to_download.url().to_string(),
+            path: Some(path),
+            status: match resumed_from {
+                Some(_) => DownloadStatus::Resumed,
+                None => DownloadStatus::Downloaded,
+            },
+            bytes,
+            duration,
+            bytes_per_second: bytes_per_second(bytes, duration),
+            final_url: Some(final_url),
+            sha256: Some(sha256),
+            resumed_from,
+            error: None,
+        }
+    }
+
+    pub(crate) fn cached(
+        file_to_download: &FileToDownload,
+        path: PathBuf,
+        duration: Duration,
+        sha256: Option<String>,
+    ) -> Self {
+        Self {
+            url: file_to_download.url().to_string(),
+            path: Some(path),
+            status: DownloadStatus::Cached,
+            bytes: 0,
+            duration,
+            bytes_per_second: 0.0,
+            final_url: None,
+            sha256,
+            resumed_from: None,
+            error: None,
+        }
+    }
+
+    pub(crate) fn failed(
+        file_to_download: &FileToDownload,
+        duration: Duration,
+        error: &DownloaderError,
+    ) -> Self {
+        Self {
+            url: file_to_download.url().to_string(),
+            // the error knows the name of the file if it failed after the name was resolved
+            path: error.file().unwrap_or(file_to_download).resolved_path(),
+            status: DownloadStatus::Failed,
+            bytes: 0,
+            duration,
+            bytes_per_second: 0.0,
+            final_url: None,
+            sha256: None,
+            resumed_from: None,
+            error: Some(error.chain()),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// Where the file was written to. Unknown if the download failed
+    /// before the name of the file was inferred from the response
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn status(&self) -> DownloadStatus {
+        self.status
+    }
+
+    /// The amount of bytes transferred over the network
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Average download speed in bytes per second
+    pub fn bytes_per_second(&self) -> f64 {
+        self.bytes_per_second
+    }
+
+    /// The url after following all redirects
+    pub fn final_url(&self) -> Option<&str> {
+        self.final_url.as_deref()
+    }
+
+    /// Hex encoded SHA-256 of the downloaded file, for decompressed downloads
+    /// it is computed from the bytes selected by [`crate::ChecksumOf`]
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    /// The size of the partial file a resumed download continued from
+    pub fn resumed_from(&self) -> Option<u64> {
+        self.resumed_from
+    }
+
+    /// The error and its causes
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+fn bytes_per_second(bytes: u64, duration: Duration) -> f64 {
+    let seconds = duration.as_secs_f64();
+    if seconds > 0.0 {
+        bytes as f64 / seconds
+    } else {
+        0.0
+    }
+}
+
+fn serialize_seconds<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> core::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
@@ -0,0 +1,66 @@
+use crate::{DownloaderError, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Makes sure that every filesystem has enough free space for all downloads into it,
+/// plus the margin that is reserved on each of them (for example for a subsequent extraction).
+pub(crate) fn check_free_space(
+    downloads: impl IntoIterator<Item = (PathBuf, u64)>,
+    margin: u64,
+) -> Result<()> {
+    let mut filesystems = HashMap::<FileSystemId, (PathBuf, u64)>::new();
+
+    for (directory, size) in downloads {
+        let existing = existing_ancestor(&directory);
+        let (_, required) = filesystems
+            .entry(filesystem_id(&existing)?)
+            .or_insert_with(|| (existing, margin));
+        *required = required.saturating_add(size);
+    }
+
+    for (directory, required) in filesystems.into_values() {
+        let available = fs4::available_space(&directory)?;
+        if available < required {
+            return DownloaderError::InsufficientSpace {
+                directory,
+                required,
+                available,
+            }
+            .into();
+        }
+    }
+
+    Ok(())
+}
+
+/// The destination directory may not exist yet, in that case we check the space
+/// of the closest parent that does.
+fn existing_ancestor(directory: &Path) -> PathBuf {
+    directory
+        .ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .map(|ancestor| ancestor.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(unix)]
+type FileSystemId = u64;
+
+#[cfg(unix)]
+fn filesystem_id(directory: &Path) -> Result<FileSystemId> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(std::fs::metadata(directory)?.dev())
+}
+
+#[cfg(not(unix))]
+type FileSystemId = PathBuf;
+
+#[cfg(not(unix))]
+fn filesystem_id(directory: &Path) -> Result<FileSystemId> {
+    let directory = std::fs::canonicalize(directory)?;
+    Ok(directory
+        .components()
+        .next()
+        .map(|prefix| PathBuf::from(prefix.as_os_str()))
+        .unwrap_or(directory))
+}
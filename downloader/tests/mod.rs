@@ -0,0 +1,811 @@
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+#[cfg(feature = "oci")]
+use downloader::OciReference;
+use downloader::{
+    download_task, ChecksumOf, Compression, DownloadPhase, DownloadStatus, DownloaderError,
+    FileToDownload, FilesToDownload,
+};
+use indicatif::MultiProgress;
+use serde_json::json;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::io::AsyncWriteExt;
+use wiremock::matchers::{header, method, path};
+#[cfg(feature = "oci")]
+use wiremock::matchers::{path_regex, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn validate_removes_duplicates() -> Result<(), Box<dyn Error>> {
+    let vm = FileToDownload::new("https://example.com/vm.zip", "third_party", "vm.zip");
+    let image = FileToDownload::new("https://example.com/image.zip", "third_party", "image.zip");
+
+    let files = FilesToDownload::new()
+        .add(vm.clone())
+        .add(image.clone())
+        .add(vm.clone())
+        .validate()?;
+
+    assert_eq!(files.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn validate_rejects_conflicting_destinations() {
+    let files = FilesToDownload::new()
+        .add(FileToDownload::new(
+            "https://example.com/v1/vm.zip",
+            "third_party",
+            "vm.zip",
+        ))
+        .add(FileToDownload::new(
+            "https://example.com/v2/vm.zip",
+            "third_party",
+            "vm.zip",
+        ));
+
+    match files.validate() {
+        Err(DownloaderError::ConflictingDestination {
+            url,
+            conflicting_url,
+            ..
+        }) => {
+            assert_eq!(url, "https://example.com/v1/vm.zip");
+            assert_eq!(conflicting_url, "https://example.com/v2/vm.zip");
+        }
+        other => panic!("Expected a conflict, got {:?}", other),
+    }
+}
+
+#[test]
+fn infer_file_name_from_url() {
+    let file = FileToDownload::from_url(
+        "https://example.com/releases/latest/My%20VM.zip?token=secret",
+        "third_party",
+    );
+    assert_eq!(file.file_name(), "My VM.zip");
+    assert_eq!(file.path(), Path::new("third_party").join("My VM.zip"));
+
+    let file =
+        FileToDownload::from_url("https://example.com/releases/..%2F..%2Fetc", "third_party");
+    assert_eq!(file.file_name(), "etc");
+
+    let file = FileToDownload::from_url("https://example.com/", "third_party");
+    assert_eq!(file.file_name(), "download");
+}
+
+#[tokio::test]
+async fn infer_file_name_from_content_disposition() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(path("/latest"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header(
+                    "Content-Disposition",
+                    "attachment; filename=\"../../image.zip\"",
+                )
+                .set_body_bytes("image"),
+        )
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    FilesToDownload::new()
+        .add(FileToDownload::from_url(
+            format!("{}/latest", server.uri()),
+            output_dir.path(),
+        ))
+        .download()
+        .await?;
+
+    assert_eq!(
+        std::fs::read(output_dir.path().join("image.zip"))?,
+        b"image"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn reuse_file_with_inferred_name() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/latest"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Content-Disposition", "attachment; filename=\"image.zip\"")
+                .set_body_bytes("image"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    let image = FileToDownload::from_url(format!("{}/latest", server.uri()), output_dir.path());
+    let files = FilesToDownload::new().add(image.clone());
+
+    let report = files.clone().download().await?;
+    assert_eq!(report.files()[0].status(), DownloadStatus::Downloaded);
+    assert_eq!(
+        report.files()[0].path(),
+        Some(output_dir.path().join("image.zip").as_path())
+    );
+
+    // the guessed name differs from the real one, the download is found by the recorded name
+    assert_eq!(image.path(), output_dir.path().join("latest"));
+    assert!(image.already_downloaded());
+
+    let report = files.download().await?;
+    assert_eq!(report.files()[0].status(), DownloadStatus::Cached);
+    assert_eq!(
+        report.files()[0].path(),
+        Some(output_dir.path().join("image.zip").as_path())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn infer_file_name_after_redirect() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(path("/latest"))
+        .respond_with(ResponseTemplate::new(302).insert_header("Location", "/releases/v1.2/vm.zip"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/releases/v1.2/vm.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("vm"))
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    FilesToDownload::new()
+        .add(FileToDownload::from_url(
+            format!("{}/latest", server.uri()),
+            output_dir.path(),
+        ))
+        .download()
+        .await?;
+
+    assert_eq!(std::fs::read(output_dir.path().join("vm.zip"))?, b"vm");
+    Ok(())
+}
+
+#[test]
+fn validate_rejects_conflicting_checksums() {
+    let vm = FileToDownload::new("https://example.com/vm.zip", "third_party", "vm.zip");
+
+    let files = FilesToDownload::new()
+        .add(vm.clone().with_sha256("aa"))
+        .add(vm.clone())
+        .add(vm.with_sha256("bb"));
+
+    assert!(matches!(
+        files.validate(),
+        Err(DownloaderError::ConflictingChecksum { .. })
+    ));
+}
+
+#[tokio::test]
+async fn download_report() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(path("/vm.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("vm"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/image.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("image"))
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    let vm = FileToDownload::new(
+        format!("{}/vm.zip", server.uri()),
+        output_dir.path(),
+        "vm.zip",
+    )
+    .with_sha256("25D4C5A2F3F2C7D3B8D1C9E1A7E8E4D94A41B4C7F1E1F5C9EEB0F1BB83B2B4A1");
+    let image = FileToDownload::new(
+        format!("{}/image.zip", server.uri()),
+        output_dir.path(),
+        "image.zip",
+    )
+    .with_sha256("6105d6cc76af400325e94d588ce511be5bfdbb73b437dc51eca43917d7a43e3d");
+    let missing = FileToDownload::new(
+        format!("{}/missing.zip", server.uri()),
+        output_dir.path(),
+        "missing.zip",
+    );
+
+    let report = FilesToDownload::new()
+        .add(vm)
+        .add(image)
+        .add(missing)
+        .download()
+        .await?;
+
+    assert!(!report.is_success());
+    assert_eq!(report.files().len(), 3);
+
+    let vm = &report.files()[0];
+    assert_eq!(vm.status(), DownloadStatus::Failed);
+    assert!(vm.error().unwrap().contains("Checksum"));
+    assert!(!vm.path().unwrap().exists());
+
+    let image = &report.files()[1];
+    assert_eq!(image.status(), DownloadStatus::Downloaded);
+    assert_eq!(image.bytes(), 5);
+    assert_eq!(
+        image.path(),
+        Some(output_dir.path().join("image.zip").as_path())
+    );
+    assert_eq!(
+        image.final_url(),
+        Some(format!("{}/image.zip", server.uri()).as_str())
+    );
+    assert_eq!(
+        image.sha256(),
+        Some("6105d6cc76af400325e94d588ce511be5bfdbb73b437dc51eca43917d7a43e3d")
+    );
+
+    let missing = &report.files()[2];
+    assert_eq!(missing.status(), DownloadStatus::Failed);
+    assert_eq!(report.failed().count(), 2);
+
+    let json = serde_json::to_value(&report)?;
+    assert_eq!(json["files"][1]["status"], "downloaded");
+    assert_eq!(json["files"][1]["bytes"], 5);
+    assert_eq!(json["files"][2]["status"], "failed");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reuse_file_downloaded_by_another_process() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/vm.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("vm"))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    let vm = FileToDownload::new(
+        format!("{}/vm.zip", server.uri()),
+        output_dir.path(),
+        "vm.zip",
+    );
+
+    // pretend that another process is downloading the same file
+    let lock = File::create(output_dir.path().join("vm.zip.lock"))?;
+    lock.lock()?;
+
+    let download = tokio::spawn(FilesToDownload::new().add(vm.clone()).download());
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    std::fs::write(vm.path(), "vm")?;
+    lock.unlock()?;
+
+    let report = download.await??;
+    assert_eq!(report.files()[0].status(), DownloadStatus::Cached);
+    assert_eq!(report.files()[0].bytes(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn resume_interrupted_download() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("image"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(header("Range", "bytes=3-"))
+        .respond_with(ResponseTemplate::new(206).set_body_bytes("ge"))
+        .mount(&server)
+        .await;
+
+    // an interrupted download left the first bytes behind
+    let output_dir = tempdir()?;
+    std::fs::write(output_dir.path().join("image.zip.part"), "ima")?;
+
+    let report = FilesToDownload::new()
+        .add(
+            FileToDownload::new(
+                format!("{}/image.zip", server.uri()),
+                output_dir.path(),
+                "image.zip",
+            )
+            .with_sha256(hex_sha256(b"image")),
+        )
+        .download()
+        .await?;
+
+    let image = &report.files()[0];
+    assert_eq!(image.status(), DownloadStatus::Resumed);
+    assert_eq!(image.resumed_from(), Some(3));
+    assert_eq!(image.bytes(), 2);
+    assert_eq!(image.sha256(), Some(hex_sha256(b"image").as_str()));
+    assert_eq!(
+        std::fs::read(output_dir.path().join("image.zip"))?,
+        b"image"
+    );
+    assert!(!output_dir.path().join("image.zip.part").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn lock_timeout() -> Result<(), Box<dyn Error>> {
+    let output_dir = tempdir()?;
+    let vm = FileToDownload::new("http://localhost/vm.zip", output_dir.path(), "vm.zip")
+        .with_lock_timeout(Duration::from_millis(200));
+
+    let lock = File::create(output_dir.path().join("vm.zip.lock"))?;
+    lock.lock()?;
+
+    let report = FilesToDownload::new().add(vm).download().await?;
+    assert_eq!(report.files()[0].status(), DownloadStatus::Failed);
+    assert!(report.files()[0].error().unwrap().contains("lock"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn insufficient_free_space() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .and(path("/vm.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("vm"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/vm.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("vm"))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    let result = FilesToDownload::new()
+        .add(FileToDownload::new(
+            format!("{}/vm.zip", server.uri()),
+            output_dir.path().join("vm"),
+            "vm.zip",
+        ))
+        .with_free_space_margin(u64::MAX / 2)
+        .download()
+        .await;
+
+    match result {
+        Err(DownloaderError::InsufficientSpace {
+            required,
+            available,
+            ..
+        }) => {
+            assert_eq!(required, u64::MAX / 2 + 2);
+            assert!(available < required);
+        }
+        other => panic!("Expected insufficient space, got {:?}", other),
+    }
+
+    // neither existing nor decompressed files are counted, nor probed
+    std::fs::create_dir_all(output_dir.path().join("vm"))?;
+    std::fs::write(output_dir.path().join("vm").join("vm.zip"), "vm")?;
+    server.reset().await;
+    Mock::given(method("HEAD"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("image"))
+        .expect(0)
+        .mount(&server)
+        .await;
+    let result = FilesToDownload::new()
+        .add(FileToDownload::new(
+            format!("{}/vm.zip", server.uri()),
+            output_dir.path().join("vm"),
+            "vm.zip",
+        ))
+        .add(
+            FileToDownload::from_url(format!("{}/image.gz", server.uri()), output_dir.path())
+                .with_decompression(Compression::Gzip),
+        )
+        .with_free_space_margin(u64::MAX / 2)
+        .download()
+        .await;
+
+    match result {
+        Err(DownloaderError::InsufficientSpace { required, .. }) => {
+            assert_eq!(required, u64::MAX / 2);
+        }
+        other => panic!("Expected insufficient space, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_carry_file_and_phase() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(path("/vm.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("vm"))
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    let missing = FileToDownload::new(
+        format!("{}/missing.zip", server.uri()),
+        output_dir.path(),
+        "missing.zip",
+    );
+    let error = download_task(missing.clone(), Arc::new(MultiProgress::new()))
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.phase(), Some(DownloadPhase::Probe));
+    assert_eq!(error.file().map(|file| file.url()), Some(missing.url()));
+    assert!(matches!(
+        error
+            .source()
+            .and_then(|source| source.downcast_ref::<DownloaderError>()),
+        Some(DownloaderError::DownloadError(_, _))
+    ));
+    assert!(error.to_string().contains(missing.url()));
+    assert!(error.source().unwrap().to_string().contains("404"));
+
+    let corrupted = FileToDownload::new(
+        format!("{}/vm.zip", server.uri()),
+        output_dir.path(),
+        "vm.zip",
+    )
+    .with_sha256("00");
+    let error = download_task(corrupted, Arc::new(MultiProgress::new()))
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.phase(), Some(DownloadPhase::Verify));
+    assert!(error.source().unwrap().to_string().contains("Checksum"));
+    assert!(!output_dir.path().join("vm.zip.part").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn post_download_actions() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(path("/tool"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("#!/bin/sh"))
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    let bin = output_dir.path().join("bin");
+    let tool = FileToDownload::new(format!("{}/tool", server.uri()), output_dir.path(), "tool")
+        .with_unix_permissions(0o755)
+        .with_post_download(move |path| {
+            let bin = bin.clone();
+            async move {
+                tokio::fs::create_dir_all(&bin).await?;
+                tokio::fs::rename(&path, bin.join("tool")).await?;
+                Ok(())
+            }
+        });
+
+    let report = FilesToDownload::new().add(tool).download().await?;
+    assert_eq!(report.files()[0].status(), DownloadStatus::Downloaded);
+
+    let moved = output_dir.path().join("bin").join("tool");
+    assert!(moved.is_file());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(moved.metadata()?.permissions().mode() & 0o777, 0o755);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "oci")]
+#[test]
+fn parse_oci_reference() -> Result<(), Box<dyn Error>> {
+    let reference = OciReference::parse("oci://ghcr.io/feenkcom/gtoolkit/vm:1.0")?;
+    assert_eq!(reference.registry(), "ghcr.io");
+    assert_eq!(reference.repository(), "feenkcom/gtoolkit/vm");
+    assert_eq!(reference.reference(), "1.0");
+
+    let reference = OciReference::parse("oci://localhost:5000/vm")?;
+    assert_eq!(reference.registry(), "localhost:5000");
+    assert_eq!(reference.repository(), "vm");
+    assert_eq!(reference.reference(), "latest");
+
+    let reference = OciReference::parse("oci://localhost:5000/vm@sha256:abcd")?;
+    assert_eq!(reference.repository(), "vm");
+    assert_eq!(reference.reference(), "sha256:abcd");
+    assert_eq!(reference.to_string(), "oci://localhost:5000/vm@sha256:abcd");
+
+    assert!(OciReference::parse("https://ghcr.io/vm").is_err());
+    assert!(OciReference::parse("oci://ghcr.io").is_err());
+    Ok(())
+}
+
+#[cfg(feature = "oci")]
+#[tokio::test]
+async fn download_oci_artifact() -> Result<(), Box<dyn Error>> {
+    let vm_digest = "sha256:e1d43a2a7ee07e23e0b6a5ef6fdd9d3eb3e4ed1fda6e8e07b1f8e0c4d2bb7b12";
+    let image_digest = "sha256:6105d6cc76af400325e94d588ce511be5bfdbb73b437dc51eca43917d7a43e3d";
+
+    let mut library = GzipEncoder::new(Vec::new());
+    library.write_all(b"library").await?;
+    library.shutdown().await?;
+    let library = library.into_inner();
+    let library_digest = format!("sha256:{}", hex_sha256(&library));
+
+    let server = MockServer::start().await;
+    let authenticate = format!(
+        "Bearer realm=\"{}/token\",service=\"registry\",scope=\"repository:feenkcom/vm:pull,push\"",
+        server.uri()
+    );
+    Mock::given(path("/token"))
+        .and(query_param("scope", "repository:feenkcom/vm:pull,push"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "token": "secret" })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/v2/feenkcom/vm/manifests/1.0"))
+        .and(header("Authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "config": {
+                "mediaType": "application/vnd.oci.empty.v1+json",
+                "digest": "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a",
+                "size": 2
+            },
+            "layers": [
+                {
+                    "mediaType": "application/zip",
+                    "digest": vm_digest,
+                    "size": 2,
+                    "annotations": { "org.opencontainers.image.title": "vm.zip" }
+                },
+                {
+                    "mediaType": "application/zip",
+                    "digest": image_digest,
+                    "size": 5,
+                    "annotations": { "org.opencontainers.image.title": "image.zip" }
+                },
+                {
+                    "mediaType": "application/gzip",
+                    "digest": library_digest,
+                    "size": library.len(),
+                    "annotations": { "org.opencontainers.image.title": "library.so.gz" }
+                }
+            ]
+        })))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(path_regex("^/v2/"))
+        .respond_with(
+            ResponseTemplate::new(401).insert_header("WWW-Authenticate", authenticate.as_str()),
+        )
+        .with_priority(10)
+        .mount(&server)
+        .await;
+    Mock::given(path(format!("/v2/feenkcom/vm/blobs/{}", image_digest)))
+        .and(header("Authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("image"))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(path(format!("/v2/feenkcom/vm/blobs/{}", vm_digest)))
+        .and(header("Authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("corrupted"))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(path(format!("/v2/feenkcom/vm/blobs/{}", library_digest)))
+        .and(header("Authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(library))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+
+    let reference = format!("oci://{}/feenkcom/vm:1.0", server.address());
+    let output_dir = tempdir()?;
+    let library = FileToDownload::from_url(&reference, output_dir.path())
+        .with_oci_layer("library.so.gz")
+        .with_decompression(Compression::Gzip);
+    assert_eq!(library.path(), output_dir.path().join("library.so"));
+
+    let report = FilesToDownload::new()
+        .add(FileToDownload::from_url(&reference, output_dir.path()).with_oci_layer("image.zip"))
+        .add(FileToDownload::new(
+            &reference,
+            output_dir.path().join("vm"),
+            "vm.zip",
+        ))
+        .add(library)
+        .download()
+        .await?;
+
+    let image = &report.files()[0];
+    assert_eq!(image.status(), DownloadStatus::Downloaded);
+    assert_eq!(
+        image.path(),
+        Some(output_dir.path().join("image.zip").as_path())
+    );
+    assert_eq!(
+        std::fs::read(output_dir.path().join("image.zip"))?,
+        b"image"
+    );
+
+    let vm = &report.files()[1];
+    assert_eq!(vm.status(), DownloadStatus::Failed);
+    assert!(vm.error().unwrap().contains("Checksum"));
+
+    // the title of a decompressed layer loses its compression extension, as predicted
+    let library = &report.files()[2];
+    assert_eq!(library.status(), DownloadStatus::Downloaded);
+    assert_eq!(
+        library.path(),
+        Some(output_dir.path().join("library.so").as_path())
+    );
+    assert_eq!(
+        std::fs::read(output_dir.path().join("library.so"))?,
+        b"library"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn decompress_while_downloading() -> Result<(), Box<dyn Error>> {
+    let library = b"library content".repeat(100);
+
+    let mut gzip = GzipEncoder::new(Vec::new());
+    gzip.write_all(&library).await?;
+    gzip.shutdown().await?;
+    let gzip = gzip.into_inner();
+
+    let mut zstd = ZstdEncoder::new(Vec::new());
+    zstd.write_all(&library).await?;
+    zstd.shutdown().await?;
+    let zstd = zstd.into_inner();
+
+    let server = MockServer::start().await;
+    Mock::given(path("/libfoo.so.gz"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(gzip.clone()))
+        .mount(&server)
+        .await;
+    Mock::given(path("/libbar.so.zst"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(zstd))
+        .mount(&server)
+        .await;
+
+    assert_eq!(
+        Compression::from_extension("libfoo.so.gz"),
+        Some(Compression::Gzip)
+    );
+    assert_eq!(Compression::from_extension("libfoo.so"), None);
+
+    let output_dir = tempdir()?;
+    let foo = FileToDownload::from_url(format!("{}/libfoo.so.gz", server.uri()), output_dir.path())
+        .with_decompression(Compression::Gzip)
+        .with_sha256(hex_sha256(&gzip));
+    let bar =
+        FileToDownload::from_url(format!("{}/libbar.so.zst", server.uri()), output_dir.path())
+            .with_decompression(Compression::Zstd)
+            .with_checksum_of(ChecksumOf::Decompressed)
+            .with_sha256(hex_sha256(&library));
+    assert_eq!(foo.path(), output_dir.path().join("libfoo.so"));
+
+    let report = FilesToDownload::new().add(foo).add(bar).download().await?;
+    assert!(report.is_success());
+    assert_eq!(report.files()[0].bytes(), gzip.len() as u64);
+    assert_eq!(std::fs::read(output_dir.path().join("libfoo.so"))?, library);
+    assert_eq!(std::fs::read(output_dir.path().join("libbar.so"))?, library);
+    Ok(())
+}
+
+fn hex_sha256(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(bytes))
+}
+
+#[tokio::test]
+async fn lockfile() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::start().await;
+    Mock::given(path("/vm.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("vm"))
+        .mount(&server)
+        .await;
+
+    let output_dir = tempdir()?;
+    let lockfile = output_dir.path().join("downloads.lock");
+    let files = FilesToDownload::new()
+        .add(FileToDownload::new(
+            format!("{}/vm.zip", server.uri()),
+            output_dir.path(),
+            "vm.zip",
+        ))
+        .with_lockfile(&lockfile);
+
+    // the first download records the file
+    assert!(files.clone().download().await?.is_success());
+    let locked: serde_json::Value = serde_json::from_slice(&std::fs::read(&lockfile)?)?;
+    let entry = &locked["files"][format!("{}/vm.zip", server.uri())];
+    assert_eq!(entry["size"], 2);
+    assert_eq!(entry["sha256"], hex_sha256(b"vm"));
+
+    // files that already exist are not downloaded again
+    let report = files.clone().download().await?;
+    assert_eq!(report.files()[0].status(), DownloadStatus::Cached);
+    std::fs::remove_file(output_dir.path().join("vm.zip"))?;
+
+    // another process recorded its own download in the meantime
+    let mut locked: serde_json::Value = serde_json::from_slice(&std::fs::read(&lockfile)?)?;
+    locked["files"]["https://example.com/other.zip"] = json!({
+        "final_url": null,
+        "size": 5,
+        "sha256": hex_sha256(b"other"),
+    });
+    std::fs::write(&lockfile, serde_json::to_vec(&locked)?)?;
+
+    // the upstream artifact silently changed
+    server.reset().await;
+    Mock::given(path("/vm.zip"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes("new vm"))
+        .mount(&server)
+        .await;
+
+    let report = files.clone().download().await?;
+    assert_eq!(report.files()[0].status(), DownloadStatus::Failed);
+    assert!(report.files()[0].error().unwrap().contains("Checksum"));
+    match report.into_result() {
+        Err(DownloaderError::DownloadsFailed(report)) => {
+            assert_eq!(report.failed().count(), 1);
+        }
+        other => panic!("Expected failed downloads, got {:?}", other),
+    }
+
+    // unless the lock is explicitly updated
+    assert!(files.clone().update_lock().await?.is_success());
+    let locked: serde_json::Value = serde_json::from_slice(&std::fs::read(&lockfile)?)?;
+    assert_eq!(
+        locked["files"]["https://example.com/other.zip"]["sha256"],
+        hex_sha256(b"other")
+    );
+    let entry = &locked["files"][format!("{}/vm.zip", server.uri())];
+    assert_eq!(entry["size"], 6);
+    assert_eq!(entry["sha256"], hex_sha256(b"new vm"));
+
+    let report = files.download().await?.into_result()?;
+    assert_eq!(report.files()[0].status(), DownloadStatus::Cached);
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() -> Result<(), Box<dyn Error>> {
+    let files = FilesToDownload::default()
+        .add(
+            FileToDownload::new("https://example.com/vm.zip", "downloads", "vm.zip")
+                .with_sha256("6105d6cc76af400325e94d588ce511be5bfdbb73b437dc51eca43917d7a43e3d")
+                .with_unix_permissions(0o755),
+        )
+        .add(
+            FileToDownload::from_url("https://example.com/image.tar.gz", "downloads")
+                .with_decompression(Compression::Gzip)
+                .with_checksum_of(ChecksumOf::Decompressed),
+        )
+        .with_lockfile("downloads.lock");
+
+    let json = serde_json::to_string(&files)?;
+    let deserialized: FilesToDownload = serde_json::from_str(&json)?;
+    assert_eq!(serde_json::to_string(&deserialized)?, json);
+
+    let file: FileToDownload = serde_json::from_str(
+        r#"{ "url": "https://example.com/vm.zip", "directory": "downloads", "file_name": null, "sha256": null, "unix_permissions": null, "oci_layer": null, "decompression": null }"#,
+    )?;
+    assert_eq!(file.lock_timeout(), Duration::from_secs(600));
+    assert_eq!(file.checksum_of(), ChecksumOf::Compressed);
+    Ok(())
+}
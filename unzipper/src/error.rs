@@ -1,3 +1,5 @@
+use crate::UnzipReport;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use zip::result::ZipError;
 
@@ -9,6 +11,166 @@ pub enum UnzipperError {
     IoError(#[from] std::io::Error),
     #[error("Zip error")]
     ZipError(#[from] ZipError),
+    #[error("Unsupported archive format of {0}")]
+    UnsupportedFormat(PathBuf),
+    #[error("Invalid glob pattern")]
+    InvalidPattern(#[source] globset::Error),
+    #[error("Failed to read the archive {archive}")]
+    ArchiveError {
+        archive: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Failed to extract {entry} from {archive} to {target}")]
+    EntryError {
+        archive: PathBuf,
+        entry: String,
+        target: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Both {other_entry} and {entry} from {archive} are extracted to {target}")]
+    EntryCollision {
+        archive: PathBuf,
+        entry: String,
+        other_entry: String,
+        target: PathBuf,
+    },
+    #[error("The link {entry} from {archive} points to {link} outside of the output folder")]
+    UnsafeSymlink {
+        archive: PathBuf,
+        entry: String,
+        target: PathBuf,
+        link: PathBuf,
+    },
+    #[error("The link {entry} from {archive} points to a path longer than {limit} bytes")]
+    SymlinkTooLong {
+        archive: PathBuf,
+        entry: String,
+        target: PathBuf,
+        limit: usize,
+    },
+    #[error("The entry {entry} from {archive} would be extracted through the link {link}")]
+    ThroughSymlink {
+        archive: PathBuf,
+        entry: String,
+        target: PathBuf,
+        link: PathBuf,
+    },
+    #[error("{archive} has more than {limit} entries")]
+    TooManyEntries { archive: PathBuf, limit: usize },
+    #[error("{entry} from {archive} is larger than {limit} bytes")]
+    EntryTooLarge {
+        archive: PathBuf,
+        entry: String,
+        limit: u64,
+    },
+    #[error("Extracting {entry} from {archive} exceeds the limit of {limit} bytes")]
+    ArchiveTooLarge {
+        archive: PathBuf,
+        entry: String,
+        limit: u64,
+    },
+    #[error("Extracting {entry} from {archive} exceeds the compression ratio of {limit}")]
+    CompressionRatioExceeded {
+        archive: PathBuf,
+        entry: String,
+        limit: f64,
+    },
+    #[error("{target} already exists, refusing to overwrite it with {entry} from {archive}")]
+    Conflict {
+        archive: PathBuf,
+        entry: String,
+        target: PathBuf,
+    },
+    #[error("{output} already exists, refusing to replace it with {archive}")]
+    OutputExists { archive: PathBuf, output: PathBuf },
+    #[error("Failed to move the extracted {archive} to {output}")]
+    OutputError {
+        archive: PathBuf,
+        output: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("{archive} contains no file {entry}")]
+    EntryNotFound { archive: PathBuf, entry: String },
+    #[error("Failed to unzip {} archive(s)", .failures.len())]
+    UnzipFailed {
+        failures: Vec<UnzipperError>,
+        /// The archives that were unzipped successfully
+        report: UnzipReport,
+    },
+}
+
+impl UnzipperError {
+    /// The archive which failed to unzip, if the error is specific to one archive
+    pub fn archive(&self) -> Option<&Path> {
+        match self {
+            Self::UnsupportedFormat(archive)
+            | Self::ArchiveError { archive, .. }
+            | Self::EntryError { archive, .. }
+            | Self::EntryCollision { archive, .. }
+            | Self::UnsafeSymlink { archive, .. }
+            | Self::SymlinkTooLong { archive, .. }
+            | Self::ThroughSymlink { archive, .. }
+            | Self::Conflict { archive, .. }
+            | Self::TooManyEntries { archive, .. }
+            | Self::EntryTooLarge { archive, .. }
+            | Self::ArchiveTooLarge { archive, .. }
+            | Self::CompressionRatioExceeded { archive, .. }
+            | Self::EntryNotFound { archive, .. }
+            | Self::OutputExists { archive, .. }
+            | Self::OutputError { archive, .. } => Some(archive.as_path()),
+            _ => None,
+        }
+    }
+
+    /// The name of the archive entry which failed to extract
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            Self::EntryError { entry, .. }
+            | Self::EntryCollision { entry, .. }
+            | Self::UnsafeSymlink { entry, .. }
+            | Self::SymlinkTooLong { entry, .. }
+            | Self::ThroughSymlink { entry, .. }
+            | Self::Conflict { entry, .. }
+            | Self::EntryTooLarge { entry, .. }
+            | Self::ArchiveTooLarge { entry, .. }
+            | Self::CompressionRatioExceeded { entry, .. }
+            | Self::EntryNotFound { entry, .. } => Some(entry.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The path the entry was being extracted to
+    pub fn target(&self) -> Option<&Path> {
+        match self {
+            Self::EntryError { target, .. }
+            | Self::EntryCollision { target, .. }
+            | Self::UnsafeSymlink { target, .. }
+            | Self::SymlinkTooLong { target, .. }
+            | Self::ThroughSymlink { target, .. }
+            | Self::Conflict { target, .. } => Some(target.as_path()),
+            _ => None,
+        }
+    }
+
+    /// The individual failures collected by [`crate::FilesToUnzip::unzip`]
+    pub fn failures(&self) -> &[UnzipperError] {
+        match self {
+            Self::UnzipFailed { failures, .. } => failures.as_slice(),
+            _ => &[],
+        }
+    }
+
+    /// The archives that were unzipped successfully despite the failures,
+    /// collected by [`crate::FilesToUnzip::unzip`]
+    pub fn report(&self) -> Option<&UnzipReport> {
+        match self {
+            Self::UnzipFailed { report, .. } => Some(report),
+            _ => None,
+        }
+    }
 }
 
 impl<T> From<UnzipperError> for std::result::Result<T, UnzipperError> {
@@ -0,0 +1,728 @@
+use crate::{
+    ArchiveFormat, ArchiveReport, EntryStatus, FileToUnzip, Limits, OverwritePolicy, Result,
+    UnzipperError,
+};
+use filetime::FileTime;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// The kinds of archive entries we know how to extract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EntryKind {
+    File,
+    Directory,
+    /// The content of the entry is the path the link points to
+    Symlink,
+}
+
+/// The longest link target that is extracted or kept while testing
+pub(crate) const MAX_LINK_LEN: usize = 4096;
+
+/// What the extraction needs to know about an entry of any archive format
+#[derive(Debug, Clone)]
+pub(crate) struct EntryMetadata {
+    pub(crate) name: String,
+    pub(crate) kind: EntryKind,
+    pub(crate) unix_mode: Option<u32>,
+    /// The declared uncompressed size
+    pub(crate) size: Option<u64>,
+    /// The declared compressed size, if the entry is compressed on its own
+    pub(crate) compressed_size: Option<u64>,
+    /// The bytes read so far from the archive file the entry is read from,
+    /// if the entry is compressed on its own
+    pub(crate) compressed_read: Option<Arc<AtomicU64>>,
+    /// The modification time as seconds since the unix epoch
+    pub(crate) modified: Option<i64>,
+    /// The CRC-32 of the uncompressed content, if the format stores it
+    pub(crate) crc32: Option<u32>,
+}
+
+/// What to do with an existing file or link
+enum Decision {
+    Overwrite,
+    Keep,
+    /// Overwrite only if the content differs
+    CompareContent,
+}
+
+/// The state of extracting a single archive shared between the archive formats.
+/// Entries are extracted through a shared reference so that they can be written in parallel
+pub(crate) struct Extraction<'a> {
+    file_to_unzip: &'a FileToUnzip,
+    report: Mutex<ArchiveReport>,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    /// Entries by their target path, used to detect collisions caused by stripping components
+    targets: HashMap<PathBuf, (String, EntryKind)>,
+    /// Symbolic links extracted by this extraction, nothing is extracted through them
+    links: HashSet<PathBuf>,
+    limits: Limits,
+    /// The size of the archive on the disk
+    archive_size: u64,
+    /// The amount of uncompressed bytes read from the archive so far
+    total_bytes: AtomicU64,
+    /// Extracting the contents of a folder changes its modification time,
+    /// so it is restored once everything is extracted
+    directory_times: Mutex<Vec<(String, PathBuf, FileTime)>>,
+}
+
+impl<'a> Extraction<'a> {
+    pub(crate) fn new(file_to_unzip: &'a FileToUnzip, format: ArchiveFormat) -> Result<Self> {
+        let include = if file_to_unzip.include.is_empty() {
+            None
+        } else {
+            Some(glob_set(&file_to_unzip.include)?)
+        };
+
+        let archive_size = std::fs::metadata(&file_to_unzip.archive)
+            .map_err(|error| file_to_unzip.archive_error(error))?
+            .len();
+
+        Ok(Self {
+            file_to_unzip,
+            report: Mutex::new(ArchiveReport::new(
+                file_to_unzip.archive.clone(),
+                file_to_unzip.output.clone(),
+                format,
+            )),
+            include,
+            exclude: glob_set(&file_to_unzip.exclude)?,
+            targets: HashMap::new(),
+            links: HashSet::new(),
+            limits: file_to_unzip.limits.unwrap_or_default(),
+            archive_size,
+            total_bytes: AtomicU64::new(0),
+            directory_times: Mutex::new(vec![]),
+        })
+    }
+
+    /// The path of the entry relative to the output folder, or None if it should not be extracted
+    pub(crate) fn selected_path(&self, path: &Path) -> Option<PathBuf> {
+        let path = enclosed_path(path)?;
+
+        // the filters are matched against the path in the archive, before stripping components
+        if let Some(include) = &self.include {
+            if !include.is_match(&path) {
+                return None;
+            }
+        }
+        if self.exclude.is_match(&path) {
+            return None;
+        }
+
+        let path = path
+            .components()
+            .skip(self.file_to_unzip.strip_components)
+            .collect::<PathBuf>();
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Where to extract the entry with the given path, or None if it should be skipped
+    pub(crate) fn target(&mut self, entry: &EntryMetadata, path: &Path) -> Result<Option<PathBuf>> {
+        let target = match self.selected_path(path) {
+            Some(path) => self.file_to_unzip.output.join(path),
+            None => {
+                self.skip();
+                return Ok(None);
+            }
+        };
+        // a link may replace another link, anything else would be written through it
+        let through_itself = usize::from(entry.kind == EntryKind::Symlink);
+        if let Some(link) = target
+            .ancestors()
+            .skip(through_itself)
+            .find(|ancestor| self.links.contains(*ancestor))
+        {
+            return UnzipperError::ThroughSymlink {
+                archive: self.file_to_unzip.archive.clone(),
+                entry: entry.name.clone(),
+                target: target.clone(),
+                link: link.to_path_buf(),
+            }
+            .into();
+        }
+
+        let strip_components = self.file_to_unzip.strip_components;
+        if strip_components > 0 {
+            if let Some((other_entry, other_kind)) = self.targets.get(&target) {
+                // folders are merged like `tar --strip-components` does
+                if entry.kind != EntryKind::Directory || *other_kind != EntryKind::Directory {
+                    return UnzipperError::EntryCollision {
+                        archive: self.file_to_unzip.archive.clone(),
+                        entry: entry.name.clone(),
+                        other_entry: other_entry.clone(),
+                        target,
+                    }
+                    .into();
+                }
+            }
+            self.targets
+                .insert(target.clone(), (entry.name.clone(), entry.kind));
+        }
+
+        if entry.kind == EntryKind::Symlink {
+            self.links.insert(target.clone());
+        }
+
+        Ok(Some(target))
+    }
+
+    pub(crate) fn extract(
+        &self,
+        entry: &EntryMetadata,
+        reader: &mut dyn Read,
+        target: &Path,
+    ) -> Result<()> {
+        let (status, bytes) = match entry.kind {
+            EntryKind::Directory => (None, self.write(entry, reader, target)?),
+            EntryKind::File => {
+                let (status, bytes) = self.extract_file(entry, reader, target)?;
+                (Some(status), bytes)
+            }
+            EntryKind::Symlink => (Some(self.extract_symlink(entry, reader, target)?), 0),
+        };
+
+        if let Some(status) = status {
+            self.report().outcome(&entry.name, target, status);
+            if status == EntryStatus::Kept {
+                return Ok(());
+            }
+        }
+
+        if let Some(modified) = entry.modified {
+            let modified = FileTime::from_unix_time(modified, 0);
+            match entry.kind {
+                EntryKind::Directory => self.directory_times.lock().unwrap().push((
+                    entry.name.clone(),
+                    target.to_path_buf(),
+                    modified,
+                )),
+                EntryKind::File => filetime::set_file_mtime(target, modified)
+                    .map_err(|error| self.file_to_unzip.entry_error(&entry.name, target, error))?,
+                EntryKind::Symlink => filetime::set_symlink_file_times(target, modified, modified)
+                    .map_err(|error| self.file_to_unzip.entry_error(&entry.name, target, error))?,
+            }
+        }
+
+        self.report().extracted(bytes);
+        Ok(())
+    }
+
+    fn extract_file(
+        &self,
+        entry: &EntryMetadata,
+        reader: &mut dyn Read,
+        target: &Path,
+    ) -> Result<(EntryStatus, u64)> {
+        let existing = match std::fs::symlink_metadata(target) {
+            Ok(existing) => existing,
+            Err(_) => return Ok((EntryStatus::Created, self.write(entry, reader, target)?)),
+        };
+
+        let overwrite = match self.decide(entry, target, &existing)? {
+            Decision::Overwrite => true,
+            Decision::Keep => false,
+            Decision::CompareContent => match entry.crc32 {
+                Some(crc32) => {
+                    !existing.is_file()
+                        || crc32_of_file(target).map_err(|error| {
+                            self.file_to_unzip.entry_error(&entry.name, target, error)
+                        })? != crc32
+                }
+                None => return self.extract_if_differs(entry, reader, target, &existing),
+            },
+        };
+
+        if overwrite {
+            Ok((EntryStatus::Overwritten, self.write(entry, reader, target)?))
+        } else {
+            Ok((EntryStatus::Kept, 0))
+        }
+    }
+
+    /// Formats like tar do not store a checksum of the entries, so the entry is extracted
+    /// next to the existing file while computing its checksum and only replaces it if they differ
+    fn extract_if_differs(
+        &self,
+        entry: &EntryMetadata,
+        reader: &mut dyn Read,
+        target: &Path,
+        existing: &std::fs::Metadata,
+    ) -> Result<(EntryStatus, u64)> {
+        let partial = partial_path(target);
+        let mut reader = Crc32Reader {
+            inner: reader,
+            hasher: crc32fast::Hasher::new(),
+        };
+        let bytes = self.write(entry, &mut reader, &partial)?;
+        let crc32 = reader.hasher.finalize();
+
+        let identical = existing.is_file()
+            && crc32_of_file(target)
+                .map_err(|error| self.file_to_unzip.entry_error(&entry.name, target, error))?
+                == crc32;
+
+        let result = if identical {
+            std::fs::remove_file(&partial).map(|_| (EntryStatus::Kept, 0))
+        } else {
+            std::fs::rename(&partial, target).map(|_| (EntryStatus::Overwritten, bytes))
+        };
+        result.map_err(|error| self.file_to_unzip.entry_error(&entry.name, target, error))
+    }
+
+    /// Decide what to do with an existing file according to the overwrite policy
+    fn decide(
+        &self,
+        entry: &EntryMetadata,
+        target: &Path,
+        existing: &std::fs::Metadata,
+    ) -> Result<Decision> {
+        Ok(match self.file_to_unzip.overwrite {
+            OverwritePolicy::Overwrite => Decision::Overwrite,
+            OverwritePolicy::SkipExisting => Decision::Keep,
+            OverwritePolicy::Newer => {
+                let existing_modified = FileTime::from_last_modification_time(existing);
+                match entry.modified {
+                    Some(modified) if FileTime::from_unix_time(modified, 0) > existing_modified => {
+                        Decision::Overwrite
+                    }
+                    _ => Decision::Keep,
+                }
+            }
+            OverwritePolicy::Differs => Decision::CompareContent,
+            OverwritePolicy::Fail => {
+                return UnzipperError::Conflict {
+                    archive: self.file_to_unzip.archive.clone(),
+                    entry: entry.name.clone(),
+                    target: target.to_path_buf(),
+                }
+                .into()
+            }
+        })
+    }
+
+    /// Write the entry to the path, enforcing the limits
+    fn write(&self, entry: &EntryMetadata, reader: &mut dyn Read, path: &Path) -> Result<u64> {
+        self.check_declared_size(entry)?;
+
+        let mut reader = self.streamed_reader(entry, reader);
+        let result = extract_entry(&mut reader, entry.kind, path, entry.unix_mode);
+        if let Some(exceeded) = reader.exceeded {
+            return Err(self.limit_error(&entry.name, exceeded));
+        }
+        #[cfg(feature = "tracing")]
+        if let Ok(written) = &result {
+            tracing::trace!(entry = entry.name, bytes = *written, "Extracted");
+        }
+        result.map_err(|error| self.file_to_unzip.entry_error(&entry.name, path, error))
+    }
+
+    /// Fail if the archive has more entries than allowed
+    pub(crate) fn check_entries(&self, entries: usize) -> Result<()> {
+        match self.limits.max_entries() {
+            Some(limit) if entries > limit => UnzipperError::TooManyEntries {
+                archive: self.file_to_unzip.archive.clone(),
+                limit,
+            }
+            .into(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail early if the declared size of the entry already exceeds the limits
+    fn check_declared_size(&self, entry: &EntryMetadata) -> Result<()> {
+        if let Some(size) = entry.size {
+            let mut empty = std::io::empty();
+            let compressed = match entry.compressed_size {
+                Some(compressed_size) => Compressed::Entry(compressed_size),
+                None => Compressed::Archive(self.archive_size),
+            };
+            let mut reader = self.limited_reader(compressed, &mut empty);
+            let total = self.total_bytes.load(Ordering::Relaxed) + size;
+            if let Some(exceeded) = reader.check(size, total) {
+                return Err(self.limit_error(&entry.name, exceeded));
+            }
+        }
+        Ok(())
+    }
+
+    /// Limit the reading of the entry. The declared compressed size can not be trusted,
+    /// so the compression ratio is computed from the compressed bytes actually read
+    fn streamed_reader<'r>(
+        &'r self,
+        entry: &'r EntryMetadata,
+        reader: &'r mut dyn Read,
+    ) -> LimitedReader<'r> {
+        let compressed = match &entry.compressed_read {
+            Some(read) => Compressed::Read {
+                start: read.load(Ordering::Relaxed),
+                read,
+            },
+            None => Compressed::Archive(self.archive_size),
+        };
+        self.limited_reader(compressed, reader)
+    }
+
+    fn limited_reader<'r>(
+        &'r self,
+        compressed: Compressed<'r>,
+        reader: &'r mut dyn Read,
+    ) -> LimitedReader<'r> {
+        LimitedReader {
+            inner: reader,
+            limits: self.limits,
+            bytes: 0,
+            total_bytes: &self.total_bytes,
+            compressed,
+            exceeded: None,
+        }
+    }
+
+    fn limit_error(&self, entry: &str, exceeded: LimitExceeded) -> UnzipperError {
+        let archive = self.file_to_unzip.archive.clone();
+        let entry = entry.to_string();
+        match exceeded {
+            LimitExceeded::TotalBytes(limit) => UnzipperError::ArchiveTooLarge {
+                archive,
+                entry,
+                limit,
+            },
+            LimitExceeded::EntryBytes(limit) => UnzipperError::EntryTooLarge {
+                archive,
+                entry,
+                limit,
+            },
+            LimitExceeded::CompressionRatio(limit) => UnzipperError::CompressionRatioExceeded {
+                archive,
+                entry,
+                limit,
+            },
+        }
+    }
+
+    fn extract_symlink(
+        &self,
+        entry: &EntryMetadata,
+        reader: &mut dyn Read,
+        target: &Path,
+    ) -> Result<EntryStatus> {
+        let too_long = || UnzipperError::SymlinkTooLong {
+            archive: self.file_to_unzip.archive.clone(),
+            entry: entry.name.clone(),
+            target: target.to_path_buf(),
+            limit: MAX_LINK_LEN,
+        };
+
+        self.check_declared_size(entry)?;
+        if entry.size.is_some_and(|size| size > MAX_LINK_LEN as u64) {
+            return Err(too_long());
+        }
+
+        // one more byte than allowed tells that the link is too long
+        let mut link = Vec::new();
+        let mut reader = self.streamed_reader(entry, reader);
+        let result = reader
+            .by_ref()
+            .take(MAX_LINK_LEN as u64 + 1)
+            .read_to_end(&mut link);
+        if let Some(exceeded) = reader.exceeded {
+            return Err(self.limit_error(&entry.name, exceeded));
+        }
+        result.map_err(|error| self.file_to_unzip.entry_error(&entry.name, target, error))?;
+        if link.len() > MAX_LINK_LEN {
+            return Err(too_long());
+        }
+        let link = String::from_utf8(link)
+            .map_err(|error| self.file_to_unzip.entry_error(&entry.name, target, error))?;
+        let link = PathBuf::from(link);
+
+        let relative_target = target
+            .strip_prefix(&self.file_to_unzip.output)
+            .unwrap_or(target);
+        if !is_enclosed_link(relative_target, &link) {
+            return UnzipperError::UnsafeSymlink {
+                archive: self.file_to_unzip.archive.clone(),
+                entry: entry.name.clone(),
+                target: target.to_path_buf(),
+                link,
+            }
+            .into();
+        }
+
+        let status = match std::fs::symlink_metadata(target) {
+            Err(_) => EntryStatus::Created,
+            Ok(existing) => match self.decide(entry, target, &existing)? {
+                Decision::Overwrite => EntryStatus::Overwritten,
+                Decision::Keep => EntryStatus::Kept,
+                Decision::CompareContent => match std::fs::read_link(target) {
+                    Ok(existing_link) if existing_link == link => EntryStatus::Kept,
+                    _ => EntryStatus::Overwritten,
+                },
+            },
+        };
+
+        if status != EntryStatus::Kept {
+            create_symlink(&link, target)
+                .map_err(|error| self.file_to_unzip.entry_error(&entry.name, target, error))?;
+        }
+        Ok(status)
+    }
+
+    pub(crate) fn skip(&self) {
+        self.report().skip();
+    }
+
+    fn report(&self) -> std::sync::MutexGuard<'_, ArchiveReport> {
+        self.report.lock().unwrap()
+    }
+
+    pub(crate) fn finish(self) -> Result<ArchiveReport> {
+        for (entry, directory, modified) in self.directory_times.into_inner().unwrap() {
+            filetime::set_file_mtime(&directory, modified)
+                .map_err(|error| self.file_to_unzip.entry_error(entry, &directory, error))?;
+        }
+        Ok(self.report.into_inner().unwrap())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LimitExceeded {
+    TotalBytes(u64),
+    EntryBytes(u64),
+    CompressionRatio(f64),
+}
+
+/// The compressed bytes the uncompressed ones are compared with for the compression ratio
+enum Compressed<'r> {
+    /// The declared compressed size of an entry compressed on its own
+    Entry(u64),
+    /// The bytes read from the archive file since the entry started
+    Read { read: &'r AtomicU64, start: u64 },
+    /// The whole archive is compressed, so it is compared with everything extracted so far
+    Archive(u64),
+}
+
+impl Compressed<'_> {
+    fn bytes(&self) -> u64 {
+        match self {
+            Self::Entry(bytes) | Self::Archive(bytes) => *bytes,
+            Self::Read { read, start } => read.load(Ordering::Relaxed) - start,
+        }
+    }
+}
+
+/// Enforces the [`Limits`] while an entry is streamed, remembering which one was exceeded
+struct LimitedReader<'r> {
+    inner: &'r mut dyn Read,
+    limits: Limits,
+    /// Bytes read from this entry
+    bytes: u64,
+    /// Bytes read from the whole archive, shared with the other entries
+    total_bytes: &'r AtomicU64,
+    compressed: Compressed<'r>,
+    exceeded: Option<LimitExceeded>,
+}
+
+impl LimitedReader<'_> {
+    fn check(&mut self, bytes: u64, total: u64) -> Option<LimitExceeded> {
+        let exceeded = if let Some(limit) =
+            self.limits.max_entry_bytes().filter(|limit| bytes > *limit)
+        {
+            Some(LimitExceeded::EntryBytes(limit))
+        } else if let Some(limit) = self.limits.max_total_bytes().filter(|limit| total > *limit) {
+            Some(LimitExceeded::TotalBytes(limit))
+        } else {
+            self.limits
+                .max_compression_ratio()
+                .filter(|limit| {
+                    let uncompressed = match self.compressed {
+                        Compressed::Archive(_) => total,
+                        _ => bytes,
+                    };
+                    uncompressed as f64 > self.compressed.bytes().max(1) as f64 * limit
+                })
+                .map(LimitExceeded::CompressionRatio)
+        };
+        self.exceeded = exceeded;
+        exceeded
+    }
+}
+
+impl Read for LimitedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        let total = self.total_bytes.fetch_add(read as u64, Ordering::Relaxed) + read as u64;
+        if self.check(self.bytes, total).is_some() {
+            return Err(std::io::Error::other("Extraction limit exceeded"));
+        }
+        Ok(read)
+    }
+}
+
+/// Computes the CRC-32 of everything read through it
+struct Crc32Reader<'r> {
+    inner: &'r mut dyn Read,
+    hasher: crc32fast::Hasher,
+}
+
+impl Read for Crc32Reader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+fn crc32_of_file(path: &Path) -> std::io::Result<u32> {
+    let mut hasher = crc32fast::Hasher::new();
+    let mut file = std::fs::File::open(path)?;
+    let mut buffer = [0u8; 8192];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finalize());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    path.with_file_name(file_name)
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(UnzipperError::InvalidPattern)?,
+        );
+    }
+    builder.build().map_err(UnzipperError::InvalidPattern)
+}
+
+/// Return a relative path that can not escape the output folder,
+/// or None if the entry is absolute or refers to a parent folder
+pub(crate) fn enclosed_path(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(component) => path.push(component),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+/// Return true if a link at the given path relative to the output folder
+/// points to somewhere within the output folder.
+/// Parent folders are only allowed at the start of the link, `a/..` could leave the output
+/// folder if `a` is itself a link
+pub(crate) fn is_enclosed_link(relative_target: &Path, link: &Path) -> bool {
+    let mut depth = relative_target
+        .parent()
+        .map(|parent| parent.components().count())
+        .unwrap_or_default();
+    let mut descended = false;
+
+    for component in link.components() {
+        match component {
+            Component::Normal(_) => descended = true,
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if descended || depth == 0 {
+                    return false;
+                }
+                depth -= 1;
+            }
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+#[cfg(unix)]
+fn create_symlink(link: &Path, target: &Path) -> std::io::Result<()> {
+    if let Some(p) = target.parent() {
+        if !p.exists() {
+            std::fs::create_dir_all(p)?;
+        }
+    }
+    // replace what a previous extraction left behind, but never follow it
+    if let Ok(metadata) = std::fs::symlink_metadata(target) {
+        if !metadata.is_dir() {
+            std::fs::remove_file(target)?;
+        }
+    }
+    std::os::unix::fs::symlink(link, target)
+}
+
+/// Symbolic links need special privileges on Windows,
+/// so like before they are extracted as files containing the path they point to
+#[cfg(not(unix))]
+fn create_symlink(link: &Path, target: &Path) -> std::io::Result<()> {
+    extract_entry(
+        &mut link.to_string_lossy().as_bytes(),
+        EntryKind::File,
+        target,
+        None,
+    )
+    .map(|_| ())
+}
+
+/// Extract a single entry to the given path returning the amount of written bytes
+pub(crate) fn extract_entry(
+    reader: &mut dyn Read,
+    kind: EntryKind,
+    output_path: &Path,
+    unix_mode: Option<u32>,
+) -> std::io::Result<u64> {
+    let mut written = 0;
+
+    match kind {
+        EntryKind::Directory => {
+            std::fs::create_dir_all(output_path)?;
+        }
+        EntryKind::File | EntryKind::Symlink => {
+            if let Some(p) = output_path.parent() {
+                if !p.exists() {
+                    std::fs::create_dir_all(p)?;
+                }
+            }
+            let mut outfile = std::fs::File::create(output_path)?;
+            written = std::io::copy(reader, &mut outfile)?;
+        }
+    }
+
+    // Get and Set permissions
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Some(mode) = unix_mode {
+            std::fs::set_permissions(output_path, std::fs::Permissions::from_mode(mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = unix_mode;
+
+    Ok(written)
+}
@@ -0,0 +1,83 @@
+use crate::{Result, UnzipperError};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The archive formats `unzipper` can extract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarBz2,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// Detect the format of the archive from its magic bytes, falling back to the extension
+    pub fn detect(archive: &Path) -> std::io::Result<Option<Self>> {
+        let mut header = Vec::with_capacity(512);
+        File::open(archive)?.take(512).read_to_end(&mut header)?;
+
+        Ok(Self::from_magic_bytes(&header).or_else(|| Self::from_extension(archive)))
+    }
+
+    pub fn from_magic_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+            Some(Self::Zip)
+        } else if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::TarXz)
+        } else if bytes.starts_with(b"BZh") {
+            Some(Self::TarBz2)
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::TarZst)
+        } else if bytes.get(257..262) == Some(b"ustar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_extension(archive: &Path) -> Option<Self> {
+        let file_name = archive.file_name()?.to_string_lossy().to_lowercase();
+
+        [
+            (".zip", Self::Zip),
+            (".tar", Self::Tar),
+            (".tar.gz", Self::TarGz),
+            (".tgz", Self::TarGz),
+            (".tar.xz", Self::TarXz),
+            (".txz", Self::TarXz),
+            (".tar.bz2", Self::TarBz2),
+            (".tbz2", Self::TarBz2),
+            (".tbz", Self::TarBz2),
+            (".tar.zst", Self::TarZst),
+            (".tzst", Self::TarZst),
+        ]
+        .into_iter()
+        .find(|(extension, _)| file_name.ends_with(extension))
+        .map(|(_, format)| format)
+    }
+
+    pub fn is_tar(&self) -> bool {
+        !matches!(self, Self::Zip)
+    }
+}
+
+/// Detect the format of the archive, failing if it is not supported
+pub(crate) fn detect_format(archive: &Path) -> Result<ArchiveFormat> {
+    ArchiveFormat::detect(archive)
+        .map_err(|error| UnzipperError::ArchiveError {
+            archive: archive.to_path_buf(),
+            source: error.into(),
+        })?
+        .ok_or_else(|| UnzipperError::UnsupportedFormat(archive.to_path_buf()))
+}
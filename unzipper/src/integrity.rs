@@ -0,0 +1,279 @@
+use crate::extract::{enclosed_path, is_enclosed_link, EntryKind, MAX_LINK_LEN};
+use crate::tar_archive::entry_kind;
+use crate::zip_archive::zip_entry_kind;
+use crate::ArchiveFormat;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// What is wrong with an archive or one of its entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Problem {
+    /// The entry is absolute or refers to a parent folder
+    UnsafePath,
+    /// The link points outside of the folder the archive is extracted to
+    UnsafeSymlink { link: PathBuf },
+    /// The content has another size than declared by the header
+    SizeMismatch { expected: u64, actual: u64 },
+    /// The content has another CRC-32 than declared by the header
+    CrcMismatch { expected: u32, actual: u32 },
+    /// The entry or the archive could not be read
+    Unreadable { message: String },
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct IntegrityProblem {
+    entry: Option<String>,
+    problem: Problem,
+}
+
+impl IntegrityProblem {
+    /// The name of the entry, or None if the problem is with the archive itself
+    pub fn entry(&self) -> Option<&str> {
+        self.entry.as_deref()
+    }
+
+    pub fn problem(&self) -> &Problem {
+        &self.problem
+    }
+}
+
+/// The result of testing an archive, see [`crate::ArchiveReader::test`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct IntegrityReport {
+    archive: PathBuf,
+    format: ArchiveFormat,
+    entries: usize,
+    bytes: u64,
+    problems: Vec<IntegrityProblem>,
+}
+
+impl IntegrityReport {
+    fn new(archive: &Path, format: ArchiveFormat) -> Self {
+        Self {
+            archive: archive.to_path_buf(),
+            format,
+            entries: 0,
+            bytes: 0,
+            problems: vec![],
+        }
+    }
+
+    fn problem(&mut self, entry: Option<&str>, problem: Problem) {
+        self.problems.push(IntegrityProblem {
+            entry: entry.map(str::to_string),
+            problem,
+        });
+    }
+
+    pub fn archive(&self) -> &Path {
+        self.archive.as_path()
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// True if no problem was found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// The amount of tested entries
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// The amount of uncompressed bytes read from the entries
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn problems(&self) -> &[IntegrityProblem] {
+        self.problems.as_slice()
+    }
+}
+
+pub(crate) fn test_zip(archive: &Path, zip: &mut ZipArchive<File>) -> IntegrityReport {
+    let mut report = IntegrityReport::new(archive, ArchiveFormat::Zip);
+
+    for i in 0..zip.len() {
+        let name = match zip.by_index_raw(i) {
+            Ok(file) => file.name().to_string(),
+            Err(error) => {
+                report.problem(None, unreadable(error));
+                continue;
+            }
+        };
+        let mut file = match zip.by_index(i) {
+            Ok(file) => file,
+            Err(error) => {
+                report.problem(Some(&name), unreadable(error));
+                continue;
+            }
+        };
+        let kind = zip_entry_kind(&file);
+        let path = file.enclosed_name().and_then(enclosed_path);
+        let (size, crc32) = (file.size(), file.crc32());
+
+        let content = Content::read(&mut file, kind == EntryKind::Symlink);
+        check_content(&mut report, &name, &content, size, Some(crc32));
+        check_path(&mut report, &name, Some(kind), path.as_deref(), &content);
+    }
+    report
+}
+
+pub(crate) fn test_tar(
+    archive: &Path,
+    format: ArchiveFormat,
+    tar: &mut tar::Archive<impl Read>,
+) -> IntegrityReport {
+    let mut report = IntegrityReport::new(archive, format);
+
+    let entries = match tar.entries() {
+        Ok(entries) => entries,
+        Err(error) => {
+            report.problem(None, unreadable(error));
+            return report;
+        }
+    };
+    for entry in entries {
+        // the rest of a corrupted stream can not be read
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                report.problem(None, unreadable(error));
+                break;
+            }
+        };
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        let kind = entry_kind(entry.header().entry_type());
+        let path = entry.path().ok().and_then(|path| enclosed_path(&path));
+        let link = entry
+            .link_name_bytes()
+            .map(|link| link.into_owned())
+            .unwrap_or_default();
+        let size = entry.size();
+
+        let mut content = Content::read(&mut entry, false);
+        // tar stores the path the link points to in the header instead of the content
+        content.head = link;
+        check_content(&mut report, &name, &content, size, None);
+        check_path(&mut report, &name, kind, path.as_deref(), &content);
+        if content.error.is_some() {
+            break;
+        }
+    }
+    report
+}
+
+fn check_content(
+    report: &mut IntegrityReport,
+    name: &str,
+    content: &Content,
+    size: u64,
+    crc32: Option<u32>,
+) {
+    report.entries += 1;
+    report.bytes += content.bytes;
+
+    // the zip reader fails at the end of an entry with a wrong checksum,
+    // so a failure after reading everything is reported as what caused it
+    if content.bytes > size || (content.error.is_none() && content.bytes != size) {
+        report.problem(
+            Some(name),
+            Problem::SizeMismatch {
+                expected: size,
+                actual: content.bytes,
+            },
+        );
+    } else if let Some(expected) =
+        crc32.filter(|expected| content.bytes == size && *expected != content.crc32)
+    {
+        report.problem(
+            Some(name),
+            Problem::CrcMismatch {
+                expected,
+                actual: content.crc32,
+            },
+        );
+    } else if let Some(error) = &content.error {
+        report.problem(Some(name), unreadable(error));
+    }
+}
+
+fn check_path(
+    report: &mut IntegrityReport,
+    name: &str,
+    kind: Option<EntryKind>,
+    path: Option<&Path>,
+    content: &Content,
+) {
+    let Some(path) = path else {
+        report.problem(Some(name), Problem::UnsafePath);
+        return;
+    };
+
+    // the target of a link that could not be read completely is unknown
+    if kind == Some(EntryKind::Symlink) && content.error.is_none() {
+        let link = PathBuf::from(String::from_utf8_lossy(&content.head).to_string());
+        if !is_enclosed_link(path, &link) {
+            report.problem(Some(name), Problem::UnsafeSymlink { link });
+        }
+    }
+}
+
+fn unreadable(error: impl std::fmt::Display) -> Problem {
+    Problem::Unreadable {
+        message: error.to_string(),
+    }
+}
+
+/// What was read from an entry until its end or the first error
+struct Content {
+    bytes: u64,
+    crc32: u32,
+    /// The start of the content, only kept for links
+    head: Vec<u8>,
+    error: Option<std::io::Error>,
+}
+
+impl Content {
+    fn read(reader: &mut dyn Read, keep_head: bool) -> Self {
+        let mut hasher = crc32fast::Hasher::new();
+        let mut bytes = 0;
+        let mut head = vec![];
+        let mut buffer = [0u8; 8192];
+
+        let error = loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break None,
+                Ok(read) => {
+                    hasher.update(&buffer[..read]);
+                    bytes += read as u64;
+                    if keep_head && head.len() < MAX_LINK_LEN {
+                        head.extend_from_slice(&buffer[..read.min(MAX_LINK_LEN - head.len())]);
+                    }
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) => break Some(error),
+            }
+        };
+
+        Self {
+            bytes,
+            crc32: hasher.finalize(),
+            head,
+            error,
+        }
+    }
+}
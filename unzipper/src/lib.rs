@@ -1,16 +1,57 @@
 mod error;
+mod extract;
+mod format;
+mod integrity;
+mod limits;
+mod overwrite;
+mod reader;
+mod report;
+mod staging;
+mod tar_archive;
+mod zip_archive;
 
+use futures::channel::oneshot;
 use futures::{stream, StreamExt};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use std::path::{Path, PathBuf};
-use zip::ZipArchive;
+use tar_archive::extract_tar;
+use zip_archive::extract_zip;
 
 pub use error::{Result, UnzipperError};
+pub use extract::EntryKind;
+pub use format::ArchiveFormat;
+pub use integrity::{IntegrityProblem, IntegrityReport, Problem};
+pub use limits::Limits;
+pub use overwrite::OverwritePolicy;
+pub use reader::{ArchiveReader, EntryInfo, EntryReader};
+pub use report::{ArchiveReport, EntryReport, EntryStatus, UnzipReport};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FileToUnzip {
     archive: PathBuf,
     output: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    format: Option<ArchiveFormat>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    strip_components: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    include: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    exclude: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    limits: Option<Limits>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    overwrite: OverwritePolicy,
+    #[cfg_attr(feature = "serde", serde(default))]
+    threads: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    atomic: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    replace_output: bool,
 }
 
 impl FileToUnzip {
@@ -18,34 +59,163 @@ impl FileToUnzip {
         Self {
             archive: archive.into(),
             output: output.into(),
+            format: None,
+            strip_components: 0,
+            include: vec![],
+            exclude: vec![],
+            limits: None,
+            overwrite: OverwritePolicy::default(),
+            threads: 1,
+            atomic: false,
+            replace_output: false,
         }
     }
 
+    /// Extract the archive as the given format instead of detecting it
+    pub fn with_format(mut self, format: ArchiveFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Drop the given amount of leading path components from every entry,
+    /// like `tar --strip-components`. Entries that become empty are skipped
+    pub fn strip_components(mut self, strip_components: usize) -> Self {
+        self.strip_components = strip_components;
+        self
+    }
+
+    /// Only extract entries whose path in the archive matches the glob pattern,
+    /// `*` does not cross folders, use `**` for that
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Do not extract entries whose path in the archive matches the glob pattern
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Limit the resources used by the extraction, overriding the limits of [`FilesToUnzip`]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// What to do with files that already exist in the output folder
+    pub fn with_overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Extract the files of the archive on the given amount of threads.
+    /// Only zip archives can be read in parallel, tarballs are a single stream
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Extract into a staging folder next to the output folder and move it onto the output
+    /// folder only once every entry is extracted, so that a failure leaves nothing behind.
+    /// Fails if the output folder exists and is not empty, unless it is replaced
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// Replace an existing output folder by the atomic extraction, see [`FileToUnzip::atomic`]
+    pub fn replace_output(mut self, replace_output: bool) -> Self {
+        self.replace_output = replace_output;
+        self
+    }
+
+    pub fn archive(&self) -> &Path {
+        self.archive.as_path()
+    }
+
     pub fn output(&self) -> &Path {
         self.output.as_path()
     }
+
+    /// The format of the archive, either the explicitly given one or detected from the archive
+    pub fn format(&self) -> Result<ArchiveFormat> {
+        match self.format {
+            Some(format) => Ok(format),
+            None => format::detect_format(&self.archive),
+        }
+    }
+
+    pub(crate) fn archive_error(
+        &self,
+        error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> UnzipperError {
+        UnzipperError::ArchiveError {
+            archive: self.archive.clone(),
+            source: error.into(),
+        }
+    }
+
+    pub(crate) fn entry_error(
+        &self,
+        entry: impl Into<String>,
+        target: &Path,
+        error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> UnzipperError {
+        UnzipperError::EntryError {
+            archive: self.archive.clone(),
+            entry: entry.into(),
+            target: target.to_path_buf(),
+            source: error.into(),
+        }
+    }
 }
 
+/// How many archives are extracted at the same time by default
+const DEFAULT_CONCURRENCY: usize = 2;
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FilesToUnzip {
     files: Vec<FileToUnzip>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    limits: Option<Limits>,
+    #[cfg_attr(feature = "serde", serde(default = "default_concurrency"))]
+    concurrency: usize,
+}
+
+#[cfg(feature = "serde")]
+fn default_concurrency() -> usize {
+    DEFAULT_CONCURRENCY
+}
+
+impl Default for FilesToUnzip {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FilesToUnzip {
     pub fn new() -> Self {
-        Self { files: vec![] }
+        Self {
+            files: vec![],
+            limits: None,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
     }
 
     pub fn from(files: impl IntoIterator<Item = FileToUnzip>) -> Self {
         Self {
             files: files.into_iter().collect::<Vec<FileToUnzip>>(),
+            ..Self::new()
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn add(self, file_to_unzip: FileToUnzip) -> Self {
         let mut files = self.files.clone();
         files.push(file_to_unzip);
-        Self { files }
+        Self { files, ..self }
     }
 
     pub fn maybe_add(self, file_to_unzip: Option<FileToUnzip>) -> Self {
@@ -63,14 +233,26 @@ impl FilesToUnzip {
     pub fn extend(self, files_to_unzip: Self) -> Self {
         let mut files = self.files.clone();
         files.extend(files_to_unzip.files);
-        Self { files }
+        Self { files, ..self }
+    }
+
+    /// Limit the resources used by the extraction of every archive that has no limits of its own
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Extract up to the given amount of archives at the same time, each on its own thread
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
     }
 
     pub fn is_empty(&self) -> bool {
         self.files.is_empty()
     }
 
-    pub async fn unzip(self) -> Result<()> {
+    pub async fn unzip(self) -> Result<UnzipReport> {
         let multibar = MultiProgress::new();
         let all_zips_pb = multibar.add(ProgressBar::new(self.files.len() as u64));
 
@@ -82,39 +264,110 @@ impl FilesToUnzip {
         all_zips_pb.set_message("total  ");
         all_zips_pb.tick();
 
-        // Set up a future to iterate over tasks and run up to 2 at a time.
-        let tasks = stream::iter(&self.files).enumerate().for_each_concurrent(
-            Some(2),
-            |(_i, file_to_unzip)| async {
+        // Set up a future to iterate over tasks and run up to `concurrency` at a time.
+        let limits = self.limits;
+        let concurrency = self.concurrency.max(1);
+        let results = stream::iter(self.files)
+            .map(|mut file_to_unzip| {
+                file_to_unzip.limits = file_to_unzip.limits.or(limits);
+
                 // Clone multibar and main_pb.  We will move the clones into each task.
                 let multibar = multibar.clone();
                 let main_pb = all_zips_pb.clone();
-                let file_to_unzip = file_to_unzip.clone();
 
-                futures::future::lazy(|_| unzip_task(file_to_unzip.clone(), multibar))
-                    .await
-                    .expect(format!("Failed to unzip {:?}", &file_to_unzip).as_str());
+                async move {
+                    let result = spawn_unzip_task(file_to_unzip, multibar).await;
+                    main_pb.inc(1);
+                    result
+                }
+            })
+            .buffered(concurrency)
+            .collect::<Vec<Result<ArchiveReport>>>()
+            .await;
 
-                main_pb.inc(1);
-            },
-        );
+        let mut archives = vec![];
+        let mut failures = vec![];
+        for result in results {
+            match result {
+                Ok(archive) => archives.push(archive),
+                Err(error) => failures.push(error),
+            }
+        }
 
-        // Wait for the tasks to finish.
-        tasks.await;
+        if !failures.is_empty() {
+            all_zips_pb.abandon_with_message("failed");
+            return UnzipperError::UnzipFailed {
+                failures,
+                report: UnzipReport::new(archives),
+            }
+            .into();
+        }
 
         // Change the message on the overall progress indicator.
         all_zips_pb.finish_with_message("done");
-        Ok(())
+        Ok(UnzipReport::new(archives))
     }
 }
 
-pub fn unzip_task(file_to_unzip: FileToUnzip, multibar: MultiProgress) -> Result<()> {
-    let file = std::fs::File::open(&file_to_unzip.archive)?;
-    let mut archive = ZipArchive::new(file)?;
+/// Extract the archive on its own thread so that it neither blocks the executor
+/// nor waits for the other archives
+async fn spawn_unzip_task(
+    file_to_unzip: FileToUnzip,
+    multibar: MultiProgress,
+) -> Result<ArchiveReport> {
+    let archive = file_to_unzip.archive.clone();
+    let (sender, receiver) = oneshot::channel();
+
+    #[cfg(feature = "tracing")]
+    let span = tracing::Span::current();
+    std::thread::Builder::new()
+        .name("unzip".to_string())
+        .spawn(move || {
+            #[cfg(feature = "tracing")]
+            let _entered = span.enter();
+            // the receiver is only gone if the unzip future was dropped
+            let _ = sender.send(unzip_task(file_to_unzip, multibar));
+        })
+        .map_err(|error| UnzipperError::ArchiveError {
+            archive: archive.clone(),
+            source: error.into(),
+        })?;
+
+    receiver.await.unwrap_or_else(|_| {
+        Err(UnzipperError::ArchiveError {
+            archive,
+            source: "the extraction thread panicked".into(),
+        })
+    })
+}
+
+/// List the entries of the archive, detecting its format
+pub fn list(archive: impl Into<PathBuf>) -> Result<Vec<EntryInfo>> {
+    ArchiveReader::open(archive)?.entries()
+}
 
-    // Create the ProgressBar with the acquired size from before
+/// Read every entry of the archive, detecting its format, and report what is wrong with it
+/// without writing anything
+pub fn test(archive: impl Into<PathBuf>) -> Result<IntegrityReport> {
+    ArchiveReader::open(archive)?.test()
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "unzip",
+        skip_all,
+        fields(archive = %file_to_unzip.archive.display(), output = %file_to_unzip.output.display())
+    )
+)]
+pub fn unzip_task(file_to_unzip: FileToUnzip, multibar: MultiProgress) -> Result<ArchiveReport> {
+    let format = file_to_unzip.format()?;
+    let file = std::fs::File::open(&file_to_unzip.archive)
+        .map_err(|error| file_to_unzip.archive_error(error))?;
+
+    // Create the ProgressBar, its length is set by the extraction of the specific format
     // and add it to the multi-bar
-    let progress_bar = multibar.add(ProgressBar::new(archive.len() as u64));
+    let progress_bar = multibar.add(ProgressBar::new(0));
 
     // Set Style to the ProgressBar
     progress_bar.set_style(
@@ -128,47 +381,33 @@ pub fn unzip_task(file_to_unzip: FileToUnzip, multibar: MultiProgress) -> Result
     progress_bar.set_message(
         file_to_unzip
             .archive
-            .clone()
             .file_name()
-            .unwrap()
+            .unwrap_or_default()
             .to_string_lossy()
             .to_string(),
     );
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-
-        let output_path = match file.enclosed_name() {
-            Some(path) => file_to_unzip.output.join(path),
-            None => continue,
-        };
-
-        if (&*file.name()).ends_with('/') {
-            std::fs::create_dir_all(&output_path).unwrap();
-        } else {
-            if let Some(p) = output_path.parent() {
-                if !p.exists() {
-                    std::fs::create_dir_all(&p).unwrap();
-                }
-            }
-            let mut outfile = std::fs::File::create(&output_path).unwrap();
-            std::io::copy(&mut file, &mut outfile).unwrap();
-        }
-
-        // Get and Set permissions
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-
-            if let Some(mode) = file.unix_mode() {
-                std::fs::set_permissions(&output_path, std::fs::Permissions::from_mode(mode)).unwrap();
-            }
-        }
-        progress_bar.inc(1)
-    }
+    let extract = |file_to_unzip: &FileToUnzip| match format {
+        ArchiveFormat::Zip => extract_zip(file_to_unzip, file, &progress_bar),
+        _ => extract_tar(file_to_unzip, format, file, &progress_bar),
+    };
+    let report = if file_to_unzip.atomic {
+        staging::extract_atomically(&file_to_unzip, extract)?
+    } else {
+        extract(&file_to_unzip)?
+    };
 
     // Finish the progress bar to prevent glitches
     progress_bar.finish();
 
-    Ok(())
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        format = ?format,
+        entries = report.entries(),
+        skipped = report.skipped(),
+        bytes = report.bytes(),
+        "Extracted the archive"
+    );
+
+    Ok(report)
 }
@@ -0,0 +1,63 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Protects against zip bombs and archives that are too large to extract.
+/// The limits are checked against the declared sizes before extracting and
+/// enforced while streaming the entries, since declared sizes can not be trusted
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Limits {
+    max_total_bytes: Option<u64>,
+    max_entry_bytes: Option<u64>,
+    max_entries: Option<usize>,
+    max_compression_ratio: Option<f64>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum amount of uncompressed bytes extracted from an archive
+    pub fn with_max_total_bytes(mut self, bytes: u64) -> Self {
+        self.max_total_bytes = Some(bytes);
+        self
+    }
+
+    /// Maximum uncompressed size of a single entry
+    pub fn with_max_entry_bytes(mut self, bytes: u64) -> Self {
+        self.max_entry_bytes = Some(bytes);
+        self
+    }
+
+    /// Maximum amount of entries in an archive, including the ones that are not extracted
+    pub fn with_max_entries(mut self, entries: usize) -> Self {
+        self.max_entries = Some(entries);
+        self
+    }
+
+    /// Maximum ratio between the uncompressed and compressed size.
+    /// Zip archives compress every entry on its own so the ratio is checked per entry,
+    /// compressed tar archives are checked as a whole
+    pub fn with_max_compression_ratio(mut self, ratio: f64) -> Self {
+        self.max_compression_ratio = Some(ratio);
+        self
+    }
+
+    pub fn max_total_bytes(&self) -> Option<u64> {
+        self.max_total_bytes
+    }
+
+    pub fn max_entry_bytes(&self) -> Option<u64> {
+        self.max_entry_bytes
+    }
+
+    pub fn max_entries(&self) -> Option<usize> {
+        self.max_entries
+    }
+
+    pub fn max_compression_ratio(&self) -> Option<f64> {
+        self.max_compression_ratio
+    }
+}
@@ -0,0 +1,20 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// What to do when an extracted file or link already exists in the output folder.
+/// Existing folders are always merged with the extracted ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OverwritePolicy {
+    #[default]
+    Overwrite,
+    /// Keep the existing files untouched
+    SkipExisting,
+    /// Only overwrite files that were modified before the archive entry
+    Newer,
+    /// Only overwrite files whose CRC-32 differs from the archive entry
+    Differs,
+    /// Fail with [`crate::UnzipperError::Conflict`]
+    Fail,
+}
@@ -0,0 +1,276 @@
+use crate::extract::{enclosed_path, extract_entry, EntryKind};
+use crate::integrity::{test_tar, test_zip};
+use crate::tar_archive::{decoder, entry_kind, tar_entry_info};
+use crate::zip_archive::{zip_entry_info, zip_entry_kind};
+use crate::{ArchiveFormat, IntegrityReport, Result, UnzipperError};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zip::ZipArchive;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Reads single entries of an archive without extracting the whole archive.
+///
+/// Entries are looked up by their path in the archive with the same rules as the extraction,
+/// entries that would escape the output folder can not be read.
+/// Only files can be read, not folders or links
+pub struct ArchiveReader {
+    archive: PathBuf,
+    format: ArchiveFormat,
+    zip: Option<ZipArchive<File>>,
+    /// Tarballs are a single stream, so they are read again from the start for every entry
+    tar: Option<tar::Archive<Box<dyn Read + Send>>>,
+}
+
+impl ArchiveReader {
+    /// Open the archive, detecting its format
+    pub fn open(archive: impl Into<PathBuf>) -> Result<Self> {
+        let archive = archive.into();
+        let format = crate::format::detect_format(&archive)?;
+        Self::open_as(archive, format)
+    }
+
+    /// Open the archive as the given format instead of detecting it
+    pub fn open_as(archive: impl Into<PathBuf>, format: ArchiveFormat) -> Result<Self> {
+        let archive = archive.into();
+        let zip = if format == ArchiveFormat::Zip {
+            let file = File::open(&archive).map_err(|error| archive_error(&archive, error))?;
+            Some(ZipArchive::new(file).map_err(|error| archive_error(&archive, error))?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            archive,
+            format,
+            zip,
+            tar: None,
+        })
+    }
+
+    pub fn archive(&self) -> &Path {
+        self.archive.as_path()
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// List the entries of the archive in their order, without reading their content.
+    /// Tar entries other than files, folders and symbolic links are left out
+    pub fn entries(&mut self) -> Result<Vec<EntryInfo>> {
+        let error = |error: std::io::Error| archive_error(&self.archive, error);
+
+        if let Some(zip) = self.zip.as_mut() {
+            return (0..zip.len())
+                .map(|i| {
+                    zip.by_index_raw(i)
+                        .map(|file| zip_entry_info(&file))
+                        .map_err(|error| archive_error(&self.archive, error))
+                })
+                .collect();
+        }
+
+        let file = File::open(&self.archive).map_err(error)?;
+        let archive = self.tar.insert(tar::Archive::new(
+            decoder(self.format, file).map_err(error)?,
+        ));
+
+        let mut entries = vec![];
+        for entry in archive.entries().map_err(error)? {
+            let entry = entry.map_err(error)?;
+            if let Some(info) = tar_entry_info(&entry).map_err(error)? {
+                entries.push(info);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Read every entry to its end, checking the sizes and CRC-32 declared by the headers
+    /// and that no entry would escape the output folder, without writing anything.
+    /// Fails only if the archive can not be opened, everything else is reported
+    pub fn test(&mut self) -> Result<IntegrityReport> {
+        if let Some(zip) = self.zip.as_mut() {
+            return Ok(test_zip(&self.archive, zip));
+        }
+
+        let error = |error: std::io::Error| archive_error(&self.archive, error);
+        let file = File::open(&self.archive).map_err(error)?;
+        let archive = self.tar.insert(tar::Archive::new(
+            decoder(self.format, file).map_err(error)?,
+        ));
+        Ok(test_tar(&self.archive, self.format, archive))
+    }
+
+    /// Stream the content of the file with the given path in the archive
+    pub fn entry(&mut self, name: impl AsRef<Path>) -> Result<EntryReader<'_>> {
+        let name = name.as_ref();
+        let not_found = || UnzipperError::EntryNotFound {
+            archive: self.archive.clone(),
+            entry: name.to_string_lossy().to_string(),
+        };
+        let wanted = enclosed_path(name).ok_or_else(not_found)?;
+        let error = |error: std::io::Error| archive_error(&self.archive, error);
+
+        if let Some(zip) = self.zip.as_mut() {
+            let index = (0..zip.len())
+                .find(|i| {
+                    zip.by_index_raw(*i).is_ok_and(|file| {
+                        zip_entry_kind(&file) == EntryKind::File
+                            && file.enclosed_name().and_then(enclosed_path).as_ref()
+                                == Some(&wanted)
+                    })
+                })
+                .ok_or_else(not_found)?;
+            let file = zip
+                .by_index(index)
+                .map_err(|error| archive_error(&self.archive, error))?;
+
+            return Ok(EntryReader {
+                name: file.name().to_string(),
+                reader: Box::new(file),
+            });
+        }
+
+        let file = File::open(&self.archive).map_err(error)?;
+        let archive = self.tar.insert(tar::Archive::new(
+            decoder(self.format, file).map_err(error)?,
+        ));
+
+        for entry in archive.entries().map_err(error)? {
+            let entry = entry.map_err(error)?;
+            if entry_kind(entry.header().entry_type()) != Some(EntryKind::File) {
+                continue;
+            }
+            let path = entry.path().map_err(error)?.into_owned();
+            if enclosed_path(&path).as_ref() == Some(&wanted) {
+                return Ok(EntryReader {
+                    name: path.to_string_lossy().to_string(),
+                    reader: Box::new(entry),
+                });
+            }
+        }
+        Err(not_found())
+    }
+
+    /// Read the content of the file with the given path in the archive
+    pub fn read(&mut self, name: impl AsRef<Path>) -> Result<Vec<u8>> {
+        let mut content = vec![];
+        let mut entry = self.entry(name)?;
+        let result = entry.read_to_end(&mut content);
+        drop(entry);
+
+        result
+            .map(|_| content)
+            .map_err(|error| archive_error(&self.archive, error))
+    }
+
+    /// Read the content of the file with the given path in the archive as UTF-8
+    pub fn read_to_string(&mut self, name: impl AsRef<Path>) -> Result<String> {
+        String::from_utf8(self.read(name)?).map_err(|error| archive_error(&self.archive, error))
+    }
+
+    /// Extract the file with the given path in the archive to the given path,
+    /// returning the amount of written bytes
+    pub fn extract_to(&mut self, name: impl AsRef<Path>, target: impl AsRef<Path>) -> Result<u64> {
+        let target = target.as_ref();
+        let mut entry = self.entry(name)?;
+        let entry_name = entry.name.clone();
+        let result = extract_entry(&mut entry, EntryKind::File, target, None);
+        drop(entry);
+
+        result.map_err(|error| UnzipperError::EntryError {
+            archive: self.archive.clone(),
+            entry: entry_name,
+            target: target.to_path_buf(),
+            source: error.into(),
+        })
+    }
+}
+
+fn archive_error(
+    archive: &Path,
+    error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> UnzipperError {
+    UnzipperError::ArchiveError {
+        archive: archive.to_path_buf(),
+        source: error.into(),
+    }
+}
+
+/// Describes an entry of an archive, see [`ArchiveReader::entries`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EntryInfo {
+    pub(crate) name: String,
+    pub(crate) kind: EntryKind,
+    pub(crate) size: u64,
+    pub(crate) compressed_size: Option<u64>,
+    pub(crate) crc32: Option<u32>,
+    /// Seconds since the unix epoch
+    pub(crate) modified: Option<i64>,
+    pub(crate) unix_mode: Option<u32>,
+}
+
+impl EntryInfo {
+    /// The path of the entry in the archive
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
+
+    /// The uncompressed size
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The compressed size, only zip archives compress their entries on their own
+    pub fn compressed_size(&self) -> Option<u64> {
+        self.compressed_size
+    }
+
+    /// The CRC-32 of the uncompressed content, only zip archives store it
+    pub fn crc32(&self) -> Option<u32> {
+        self.crc32
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified.map(|modified| {
+            if modified >= 0 {
+                UNIX_EPOCH + Duration::from_secs(modified as u64)
+            } else {
+                UNIX_EPOCH - Duration::from_secs(modified.unsigned_abs())
+            }
+        })
+    }
+
+    /// The unix mode as stored in the archive, tar archives only store the permissions
+    pub fn unix_mode(&self) -> Option<u32> {
+        self.unix_mode
+    }
+}
+
+/// The content of a single archive entry, see [`ArchiveReader::entry`]
+pub struct EntryReader<'a> {
+    name: String,
+    reader: Box<dyn Read + 'a>,
+}
+
+impl EntryReader<'_> {
+    /// The path of the entry in the archive
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl Read for EntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
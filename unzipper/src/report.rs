@@ -0,0 +1,154 @@
+use crate::ArchiveFormat;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Describes what was extracted from every archive of a [`crate::FilesToUnzip`] batch.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct UnzipReport {
+    archives: Vec<ArchiveReport>,
+}
+
+impl UnzipReport {
+    pub(crate) fn new(archives: Vec<ArchiveReport>) -> Self {
+        Self { archives }
+    }
+
+    pub fn archives(&self) -> &[ArchiveReport] {
+        self.archives.as_slice()
+    }
+
+    /// Total amount of extracted entries
+    pub fn entries(&self) -> usize {
+        self.archives.iter().map(|archive| archive.entries()).sum()
+    }
+
+    /// Total amount of skipped entries
+    pub fn skipped(&self) -> usize {
+        self.archives.iter().map(|archive| archive.skipped()).sum()
+    }
+}
+
+/// What happened to an extracted file or link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum EntryStatus {
+    Created,
+    Overwritten,
+    /// The existing file was kept because of the [`crate::OverwritePolicy`]
+    Kept,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EntryReport {
+    entry: String,
+    path: PathBuf,
+    status: EntryStatus,
+}
+
+impl EntryReport {
+    /// The name of the entry in the archive
+    pub fn entry(&self) -> &str {
+        self.entry.as_str()
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    pub fn status(&self) -> EntryStatus {
+        self.status
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ArchiveReport {
+    archive: PathBuf,
+    output: PathBuf,
+    format: ArchiveFormat,
+    entries: usize,
+    skipped: usize,
+    bytes: u64,
+    outcomes: Vec<EntryReport>,
+}
+
+impl ArchiveReport {
+    pub(crate) fn new(archive: PathBuf, output: PathBuf, format: ArchiveFormat) -> Self {
+        Self {
+            archive,
+            output,
+            format,
+            entries: 0,
+            skipped: 0,
+            bytes: 0,
+            outcomes: vec![],
+        }
+    }
+
+    pub(crate) fn extracted(&mut self, bytes: u64) {
+        self.entries += 1;
+        self.bytes += bytes;
+    }
+
+    pub(crate) fn skip(&mut self) {
+        self.skipped += 1;
+    }
+
+    pub(crate) fn outcome(&mut self, entry: &str, path: &Path, status: EntryStatus) {
+        self.outcomes.push(EntryReport {
+            entry: entry.to_string(),
+            path: path.to_path_buf(),
+            status,
+        });
+    }
+
+    /// Report the entries as extracted to the output instead of the staging folder
+    pub(crate) fn relocate(mut self, staging: &Path, output: &Path) -> Self {
+        for outcome in &mut self.outcomes {
+            if let Ok(path) = outcome.path.strip_prefix(staging) {
+                outcome.path = output.join(path);
+            }
+        }
+        self.output = output.to_path_buf();
+        self
+    }
+
+    pub fn archive(&self) -> &Path {
+        self.archive.as_path()
+    }
+
+    pub fn output(&self) -> &Path {
+        self.output.as_path()
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// The amount of extracted entries, not including the existing files that were kept
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// The amount of entries that were not extracted, because they were filtered out,
+    /// had an unsafe path, became empty after stripping components or are of an unsupported type
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// The amount of extracted bytes
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// What happened to every extracted file and link
+    pub fn outcomes(&self) -> &[EntryReport] {
+        self.outcomes.as_slice()
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{ArchiveReport, FileToUnzip, Result, UnzipperError};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Makes the names of the staging folders of one process unique
+static STAGING_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Extract the archive into a staging folder next to the output folder
+/// and move it onto the output folder once every entry is extracted.
+/// The staging folder is removed if the extraction fails
+pub(crate) fn extract_atomically(
+    file_to_unzip: &FileToUnzip,
+    extract: impl FnOnce(&FileToUnzip) -> Result<ArchiveReport>,
+) -> Result<ArchiveReport> {
+    let output = file_to_unzip.output.as_path();
+    let output_error = |error: std::io::Error| UnzipperError::OutputError {
+        archive: file_to_unzip.archive.clone(),
+        output: output.to_path_buf(),
+        source: error.into(),
+    };
+
+    // fail before extracting anything if the output could not be replaced anyway
+    if !file_to_unzip.replace_output && !is_missing_or_empty(output).map_err(output_error)? {
+        return output_exists(file_to_unzip);
+    }
+
+    let staging = sibling(output, "staging").map_err(output_error)?;
+    if let Some(parent) = staging
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent).map_err(output_error)?;
+    }
+    std::fs::create_dir(&staging).map_err(output_error)?;
+
+    let staged = FileToUnzip {
+        output: staging.clone(),
+        ..file_to_unzip.clone()
+    };
+    let result = extract(&staged).and_then(|report| {
+        publish(file_to_unzip, &staging)?;
+        Ok(report)
+    });
+
+    match result {
+        Ok(report) => Ok(report.relocate(&staging, output)),
+        Err(error) => {
+            // the original error matters more than a failed clean up
+            let _ = std::fs::remove_dir_all(&staging);
+            Err(error)
+        }
+    }
+}
+
+/// Move the staging folder onto the output folder
+fn publish(file_to_unzip: &FileToUnzip, staging: &Path) -> Result<()> {
+    let output = file_to_unzip.output.as_path();
+    let output_error = |error: std::io::Error| UnzipperError::OutputError {
+        archive: file_to_unzip.archive.clone(),
+        output: output.to_path_buf(),
+        source: error.into(),
+    };
+
+    if is_missing_or_empty(output).map_err(output_error)? {
+        if output.is_dir() {
+            std::fs::remove_dir(output).map_err(output_error)?;
+        }
+        return std::fs::rename(staging, output).map_err(output_error);
+    }
+    if !file_to_unzip.replace_output {
+        return output_exists(file_to_unzip);
+    }
+
+    // move the existing output out of the way first, so that it can be restored
+    let previous = sibling(output, "previous").map_err(output_error)?;
+    std::fs::rename(output, &previous).map_err(output_error)?;
+    if let Err(error) = std::fs::rename(staging, output) {
+        let _ = std::fs::rename(&previous, output);
+        return Err(output_error(error));
+    }
+
+    if previous.is_dir() {
+        std::fs::remove_dir_all(&previous)
+    } else {
+        std::fs::remove_file(&previous)
+    }
+    .map_err(output_error)
+}
+
+fn is_missing_or_empty(output: &Path) -> std::io::Result<bool> {
+    match std::fs::symlink_metadata(output) {
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(true),
+        Err(error) => Err(error),
+        Ok(metadata) if metadata.is_dir() => Ok(std::fs::read_dir(output)?.next().is_none()),
+        Ok(_) => Ok(false),
+    }
+}
+
+fn output_exists<T>(file_to_unzip: &FileToUnzip) -> Result<T> {
+    UnzipperError::OutputExists {
+        archive: file_to_unzip.archive.clone(),
+        output: file_to_unzip.output.clone(),
+    }
+    .into()
+}
+
+/// A hidden path next to the output folder, on the same file system so that it can be renamed
+fn sibling(output: &Path, purpose: &str) -> std::io::Result<PathBuf> {
+    let name = output.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "the output folder has no name",
+        )
+    })?;
+
+    let mut sibling = std::ffi::OsString::from(".");
+    sibling.push(name);
+    sibling.push(format!(
+        ".{purpose}-{}-{}",
+        std::process::id(),
+        STAGING_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(output.with_file_name(sibling))
+}
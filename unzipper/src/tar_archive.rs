@@ -0,0 +1,119 @@
+use crate::extract::{EntryKind, EntryMetadata, Extraction};
+use crate::{ArchiveFormat, ArchiveReport, EntryInfo, FileToUnzip, Result};
+use indicatif::ProgressBar;
+use std::fs::File;
+use std::io::Read;
+use tar::{Archive, Entry, EntryType};
+
+pub(crate) fn extract_tar(
+    file_to_unzip: &FileToUnzip,
+    format: ArchiveFormat,
+    file: File,
+    progress_bar: &ProgressBar,
+) -> Result<ArchiveReport> {
+    // the amount of entries is unknown until the whole archive is decompressed,
+    // so the progress follows the compressed bytes read from the disk instead,
+    // including the entries that are filtered out
+    progress_bar.set_length(
+        file.metadata()
+            .map_err(|error| file_to_unzip.archive_error(error))?
+            .len(),
+    );
+    let reader = progress_bar.wrap_read(file);
+
+    let mut archive =
+        Archive::new(decoder(format, reader).map_err(|error| file_to_unzip.archive_error(error))?);
+
+    let mut extraction = Extraction::new(file_to_unzip, format)?;
+
+    let entries = archive
+        .entries()
+        .map_err(|error| file_to_unzip.archive_error(error))?;
+    for (index, entry) in entries.enumerate() {
+        extraction.check_entries(index + 1)?;
+
+        let mut entry = entry.map_err(|error| file_to_unzip.archive_error(error))?;
+        let path = entry
+            .path()
+            .map_err(|error| file_to_unzip.archive_error(error))?
+            .into_owned();
+        let name = path.to_string_lossy().to_string();
+
+        let Some(kind) = entry_kind(entry.header().entry_type()) else {
+            extraction.skip();
+            continue;
+        };
+
+        let metadata = EntryMetadata {
+            name,
+            kind,
+            unix_mode: entry
+                .header()
+                .mode()
+                .ok()
+                .filter(|_| kind != EntryKind::Symlink),
+            size: entry.header().size().ok(),
+            compressed_size: None,
+            compressed_read: None,
+            modified: entry.header().mtime().ok().map(|mtime| mtime as i64),
+            crc32: None,
+        };
+
+        if let Some(target) = extraction.target(&metadata, &path)? {
+            if kind == EntryKind::Symlink {
+                // tar stores the path the link points to in the header instead of the content
+                let link = entry
+                    .link_name_bytes()
+                    .map(|link| link.into_owned())
+                    .unwrap_or_default();
+                extraction.extract(&metadata, &mut link.as_slice(), &target)?;
+            } else {
+                extraction.extract(&metadata, &mut entry, &target)?;
+            }
+        }
+    }
+
+    extraction.finish()
+}
+
+/// The kind of the entry, or None for the types we do not extract
+pub(crate) fn entry_kind(entry_type: EntryType) -> Option<EntryKind> {
+    match entry_type {
+        EntryType::Regular | EntryType::Continuous => Some(EntryKind::File),
+        EntryType::Directory => Some(EntryKind::Directory),
+        EntryType::Symlink => Some(EntryKind::Symlink),
+        _ => None,
+    }
+}
+
+/// The information about the entry, or None for the types we do not extract
+pub(crate) fn tar_entry_info(entry: &Entry<impl Read>) -> std::io::Result<Option<EntryInfo>> {
+    let header = entry.header();
+    let Some(kind) = entry_kind(header.entry_type()) else {
+        return Ok(None);
+    };
+
+    Ok(Some(EntryInfo {
+        name: entry.path()?.to_string_lossy().to_string(),
+        kind,
+        size: header.size()?,
+        // the whole archive is compressed, not the single entries
+        compressed_size: None,
+        crc32: None,
+        modified: header.mtime().ok().map(|mtime| mtime as i64),
+        unix_mode: header.mode().ok(),
+    }))
+}
+
+pub(crate) fn decoder<'a>(
+    format: ArchiveFormat,
+    reader: impl Read + Send + 'a,
+) -> std::io::Result<Box<dyn Read + Send + 'a>> {
+    Ok(match format {
+        ArchiveFormat::Tar | ArchiveFormat::Zip => Box::new(reader),
+        ArchiveFormat::TarGz => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        ArchiveFormat::TarXz => Box::new(liblzma::read::XzDecoder::new_multi_decoder(reader)),
+        ArchiveFormat::TarBz2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        ArchiveFormat::TarZst => Box::new(zstd::stream::read::Decoder::new(reader)?),
+    })
+}
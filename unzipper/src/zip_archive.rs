@@ -0,0 +1,271 @@
+use crate::extract::{EntryKind, EntryMetadata, Extraction};
+use crate::{ArchiveFormat, ArchiveReport, EntryInfo, FileToUnzip, Result};
+use chrono::{Local, NaiveDate, TimeZone};
+use indicatif::ProgressBar;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use zip::read::ZipFile;
+use zip::{DateTime, ZipArchive};
+
+/// The file type bits of a unix mode and the type of symbolic links
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Counts the bytes read from the archive file, which are the compressed bytes
+/// of the entry being read once its header is read
+struct CountingFile {
+    file: File,
+    read: Arc<AtomicU64>,
+}
+
+impl CountingFile {
+    fn new(file: File) -> Self {
+        Self {
+            file,
+            read: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Read for CountingFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.read.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl Seek for CountingFile {
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64> {
+        self.file.seek(position)
+    }
+}
+
+pub(crate) fn extract_zip(
+    file_to_unzip: &FileToUnzip,
+    file: File,
+    progress_bar: &ProgressBar,
+) -> Result<ArchiveReport> {
+    let file = CountingFile::new(file);
+    let compressed_read = file.read.clone();
+    let mut archive = ZipArchive::new(file).map_err(|error| file_to_unzip.archive_error(error))?;
+    let mut extraction = Extraction::new(file_to_unzip, ArchiveFormat::Zip)?;
+    extraction.check_entries(archive.len())?;
+
+    // the central directory lists all entries upfront,
+    // so the progress only counts the entries that will be extracted
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index_raw(i)
+            .map_err(|error| file_to_unzip.archive_error(error))?;
+        let selected = file
+            .enclosed_name()
+            .and_then(|path| extraction.selected_path(path))
+            .is_some();
+        entries.push((file.name().to_string(), selected));
+    }
+    progress_bar.set_length(entries.iter().filter(|(_, selected)| *selected).count() as u64);
+
+    // with several threads the files are extracted once all targets are known,
+    // links are created after them so that no file is written through a link
+    let threads = file_to_unzip.threads.max(1);
+    let mut files = vec![];
+    let mut links = vec![];
+
+    for (i, (entry, selected)) in entries.into_iter().enumerate() {
+        if !selected {
+            extraction.skip();
+            continue;
+        }
+
+        let mut file = archive
+            .by_index(i)
+            .map_err(|error| file_to_unzip.entry_error(&entry, &file_to_unzip.output, error))?;
+
+        let kind = zip_entry_kind(&file);
+
+        let path = match file.enclosed_name() {
+            Some(path) => path.to_path_buf(),
+            None => {
+                extraction.skip();
+                continue;
+            }
+        };
+
+        let metadata = EntryMetadata {
+            name: entry,
+            kind,
+            // applying the mode of a link would change the file it points to
+            unix_mode: file.unix_mode().filter(|_| kind != EntryKind::Symlink),
+            size: Some(file.size()),
+            compressed_size: Some(file.compressed_size()),
+            compressed_read: Some(compressed_read.clone()),
+            modified: modified(&file),
+            crc32: Some(file.crc32()),
+        };
+
+        if let Some(target) = extraction.target(&metadata, &path)? {
+            match kind {
+                EntryKind::File if threads > 1 => {
+                    files.push((i, metadata, target));
+                    continue;
+                }
+                EntryKind::Symlink if threads > 1 => {
+                    links.push((i, metadata, target));
+                    continue;
+                }
+                _ => extraction.extract(&metadata, &mut file, &target)?,
+            }
+        }
+
+        progress_bar.inc(1)
+    }
+
+    extract_in_parallel(file_to_unzip, &extraction, files, threads, progress_bar)?;
+
+    for (i, metadata, target) in links {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|error| file_to_unzip.entry_error(&metadata.name, &target, error))?;
+        extraction.extract(&metadata, &mut file, &target)?;
+        progress_bar.inc(1)
+    }
+
+    extraction.finish()
+}
+
+/// Extract the files on the given amount of threads, each reading its own copy of the archive.
+/// The first error stops all threads
+fn extract_in_parallel(
+    file_to_unzip: &FileToUnzip,
+    extraction: &Extraction,
+    files: Vec<(usize, EntryMetadata, PathBuf)>,
+    threads: usize,
+    progress_bar: &ProgressBar,
+) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+
+    let worker = || -> Result<()> {
+        let file = File::open(&file_to_unzip.archive)
+            .map_err(|error| file_to_unzip.archive_error(error))?;
+        let file = CountingFile::new(file);
+        let compressed_read = file.read.clone();
+        let mut archive =
+            ZipArchive::new(file).map_err(|error| file_to_unzip.archive_error(error))?;
+
+        while !failed.load(Ordering::Relaxed) {
+            let Some((i, metadata, target)) = files.get(next.fetch_add(1, Ordering::Relaxed))
+            else {
+                break;
+            };
+
+            // the entry is read from the archive file of this thread
+            let metadata = EntryMetadata {
+                compressed_read: Some(compressed_read.clone()),
+                ..metadata.clone()
+            };
+            let result = archive
+                .by_index(*i)
+                .map_err(|error| file_to_unzip.entry_error(&metadata.name, target, error))
+                .and_then(|mut file| extraction.extract(&metadata, &mut file, target));
+            if result.is_err() {
+                failed.store(true, Ordering::Relaxed);
+                return result;
+            }
+            progress_bar.inc(1)
+        }
+        Ok(())
+    };
+
+    std::thread::scope(|scope| {
+        let workers = (0..threads.min(files.len()))
+            .map(|_| scope.spawn(worker))
+            .collect::<Vec<_>>();
+
+        workers.into_iter().try_for_each(|worker| {
+            worker.join().unwrap_or_else(|_| {
+                Err(file_to_unzip.archive_error("an extraction thread panicked"))
+            })
+        })
+    })
+}
+
+pub(crate) fn zip_entry_kind(file: &ZipFile) -> EntryKind {
+    if file.name().ends_with('/') {
+        EntryKind::Directory
+    } else if file
+        .unix_mode()
+        .is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+    {
+        EntryKind::Symlink
+    } else {
+        EntryKind::File
+    }
+}
+
+pub(crate) fn zip_entry_info(file: &ZipFile) -> EntryInfo {
+    EntryInfo {
+        name: file.name().to_string(),
+        kind: zip_entry_kind(file),
+        size: file.size(),
+        compressed_size: Some(file.compressed_size()),
+        crc32: Some(file.crc32()),
+        modified: modified(file),
+        unix_mode: file.unix_mode(),
+    }
+}
+
+/// The extended timestamp extra field
+const EXTENDED_TIMESTAMP: u16 = 0x5455;
+
+/// The modification time of the entry as seconds since the unix epoch,
+/// preferably from the extended timestamp, otherwise from the MS-DOS date and time
+fn modified(file: &ZipFile) -> Option<i64> {
+    extended_timestamp(file.extra_data()).or_else(|| dos_timestamp(file.last_modified()))
+}
+
+fn extended_timestamp(mut extra_data: &[u8]) -> Option<i64> {
+    while extra_data.len() >= 4 {
+        let id = u16::from_le_bytes([extra_data[0], extra_data[1]]);
+        let size = u16::from_le_bytes([extra_data[2], extra_data[3]]) as usize;
+        let data = extra_data.get(4..4 + size)?;
+
+        // the first bit of the flags tells that the modification time is present
+        if id == EXTENDED_TIMESTAMP && data.len() >= 5 && data[0] & 1 == 1 {
+            return Some(i32::from_le_bytes([data[1], data[2], data[3], data[4]]) as i64);
+        }
+        extra_data = &extra_data[4 + size..];
+    }
+    None
+}
+
+/// MS-DOS timestamps have no time zone. Info-ZIP, 7-Zip and Python's zipfile write and read
+/// them as the local time of the machine, so we do the same. An archive created in another
+/// time zone is therefore off by the difference, unless it has an extended timestamp.
+fn dos_timestamp(date_time: DateTime) -> Option<i64> {
+    let local = NaiveDate::from_ymd_opt(
+        date_time.year() as i32,
+        date_time.month() as u32,
+        date_time.day() as u32,
+    )?
+    .and_hms_opt(
+        date_time.hour() as u32,
+        date_time.minute() as u32,
+        date_time.second() as u32,
+    )?;
+
+    // times skipped when the clocks move forward are shifted by the offset before the change
+    let timestamp = match Local.from_local_datetime(&local).earliest() {
+        Some(modified) => modified.timestamp(),
+        None => {
+            local.and_utc().timestamp()
+                - Local.offset_from_utc_datetime(&local).local_minus_utc() as i64
+        }
+    };
+    Some(timestamp)
+}
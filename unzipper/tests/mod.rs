@@ -1,7 +1,11 @@
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 use tempfile::tempdir;
-use unzipper::{FileToUnzip, FilesToUnzip};
+use unzipper::{
+    ArchiveFormat, ArchiveReader, ArchiveReport, EntryKind, EntryStatus, FileToUnzip, FilesToUnzip,
+    Limits, OverwritePolicy, Problem, UnzipperError,
+};
 
 #[test]
 fn unzip() -> Result<(), Box<dyn Error>> {
@@ -37,3 +41,956 @@ fn unzip() -> Result<(), Box<dyn Error>> {
     output_dir.close()?;
     Ok(())
 }
+
+#[test]
+fn unzip_collects_failures() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let corrupt_archive = output.join("corrupt.zip");
+    std::fs::write(&corrupt_archive, "not a zip")?;
+    // a file in place of the output folder makes the extraction of the entries fail
+    std::fs::write(output.join("blocked"), "")?;
+
+    let missing = FileToUnzip::new(
+        tests_dir.join("archives/missing.zip"),
+        output.join("missing"),
+    );
+    let corrupt = FileToUnzip::new(&corrupt_archive, output.join("corrupt"));
+    let blocked = FileToUnzip::new(tests_dir.join("archives/cat.zip"), output.join("blocked"));
+    let dog = FileToUnzip::new(tests_dir.join("archives/dog.zip"), output.join("dog"));
+
+    let to_unzip = FilesToUnzip::from(vec![missing.clone(), corrupt, blocked, dog.clone()]);
+
+    let error = futures::executor::block_on(to_unzip.unzip()).unwrap_err();
+    assert!(matches!(error, UnzipperError::UnzipFailed { .. }));
+
+    let failures = error.failures();
+    assert_eq!(failures.len(), 3);
+
+    let missing_failure = failures
+        .iter()
+        .find(|failure| failure.archive() == Some(missing.archive()))
+        .unwrap();
+    assert!(matches!(
+        missing_failure,
+        UnzipperError::ArchiveError { .. }
+    ));
+
+    let corrupt_failure = failures
+        .iter()
+        .find(|failure| failure.archive() == Some(corrupt_archive.as_path()))
+        .unwrap();
+    assert!(matches!(
+        corrupt_failure,
+        UnzipperError::ArchiveError { .. }
+    ));
+
+    let blocked_failure = failures
+        .iter()
+        .find(|failure| failure.entry().is_some())
+        .unwrap();
+    assert_eq!(blocked_failure.entry(), Some("cat.txt"));
+    assert_eq!(
+        blocked_failure.target(),
+        Some(output.join("blocked").join("cat.txt").as_path())
+    );
+
+    assert!(dog.output().join("dog.txt").is_file());
+    let report = error.report().unwrap();
+    assert_eq!(report.archives().len(), 1);
+    assert_eq!(report.archives()[0].archive(), dog.archive());
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn unzip_tarballs() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let tarballs = [
+        ("mice.tar", ArchiveFormat::Tar),
+        ("mice.tar.gz", ArchiveFormat::TarGz),
+        ("mice.tar.xz", ArchiveFormat::TarXz),
+        ("mice.tar.bz2", ArchiveFormat::TarBz2),
+        ("mice.tar.zst", ArchiveFormat::TarZst),
+        // detected by the magic bytes
+        ("mice-tarball", ArchiveFormat::TarGz),
+    ];
+
+    let to_unzip = FilesToUnzip::from(tarballs.iter().map(|(tarball, format)| {
+        let file = FileToUnzip::new(
+            tests_dir.join("archives").join(tarball),
+            output.join(tarball),
+        );
+        assert_eq!(file.format().unwrap(), *format);
+        file
+    }));
+
+    futures::executor::block_on(to_unzip.unzip())?;
+
+    for (tarball, _) in tarballs {
+        let mice = output.join(tarball).join("mice");
+        assert!(mice.is_dir());
+        assert!(mice.join("jerry.txt").is_file());
+        assert!(mice.join("cherie.txt").is_file());
+        assert_eq!(
+            std::fs::read_to_string(mice.join("squeak.sh"))?,
+            "#!/bin/sh\necho squeak\n"
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(mice.join("squeak.sh"))?
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn tarball_entries_can_not_escape_output() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path().join("escape");
+
+    let to_unzip = FilesToUnzip::new().add_file(tests_dir.join("archives/escape.tar.gz"), &output);
+    futures::executor::block_on(to_unzip.unzip())?;
+
+    assert!(output.join("safe.txt").is_file());
+    assert!(!output.join("absolute.txt").exists());
+    assert!(!output_dir.path().join("escape.txt").exists());
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn strip_components() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let zip = FileToUnzip::new(tests_dir.join("archives/mice.zip"), output.join("zip"))
+        .strip_components(1);
+    let tar = FileToUnzip::new(tests_dir.join("archives/mice.tar.gz"), output.join("tar"))
+        .strip_components(1);
+    let everything = FileToUnzip::new(tests_dir.join("archives/cat.zip"), output.join("cat"))
+        .strip_components(1);
+
+    let to_unzip = FilesToUnzip::from(vec![zip.clone(), tar.clone(), everything.clone()]);
+    futures::executor::block_on(to_unzip.unzip())?;
+
+    for mice in [zip.output(), tar.output()] {
+        assert!(mice.join("jerry.txt").is_file());
+        assert!(mice.join("cherie.txt").is_file());
+        assert!(!mice.join("mice").exists());
+    }
+    assert!(!everything.output().join("cat.txt").exists());
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn strip_components_collision() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+
+    let to_unzip = FileToUnzip::new(tests_dir.join("archives/collision.zip"), output_dir.path())
+        .strip_components(1);
+    let error = unzipper::unzip_task(to_unzip, indicatif::MultiProgress::new()).unwrap_err();
+
+    assert!(matches!(error, UnzipperError::EntryCollision { .. }));
+    assert_eq!(error.entry(), Some("dog/cat.txt"));
+    assert_eq!(
+        error.target(),
+        Some(output_dir.path().join("cat.txt").as_path())
+    );
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn include_and_exclude() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let zip = FileToUnzip::new(tests_dir.join("archives/mice.zip"), output.join("zip"))
+        .include("mice/*.txt")
+        .exclude("**/cherie.txt");
+    let tar = FileToUnzip::new(tests_dir.join("archives/mice.tar.xz"), output.join("tar"))
+        .include("mice/*.txt")
+        .exclude("**/cherie.txt")
+        .strip_components(1);
+
+    let to_unzip = FilesToUnzip::from(vec![zip.clone(), tar.clone()]);
+    let report = futures::executor::block_on(to_unzip.unzip())?;
+
+    assert!(zip.output().join("mice/jerry.txt").is_file());
+    assert!(!zip.output().join("mice/cherie.txt").exists());
+    assert!(tar.output().join("jerry.txt").is_file());
+    assert!(!tar.output().join("cherie.txt").exists());
+    assert!(!tar.output().join("squeak.sh").exists());
+
+    let zip_report = &report.archives()[0];
+    assert_eq!(zip_report.archive(), zip.archive());
+    assert_eq!(zip_report.format(), ArchiveFormat::Zip);
+    assert_eq!(zip_report.entries(), 1);
+    assert_eq!(zip_report.skipped(), 2);
+
+    let tar_report = &report.archives()[1];
+    assert_eq!(tar_report.format(), ArchiveFormat::TarXz);
+    assert_eq!(tar_report.entries(), 1);
+    assert_eq!(tar_report.skipped(), 3);
+
+    assert_eq!(report.entries(), 2);
+    assert_eq!(report.skipped(), 5);
+
+    let invalid = FileToUnzip::new(tests_dir.join("archives/mice.zip"), output.join("invalid"))
+        .include("mice/[");
+    let error = unzipper::unzip_task(invalid, indicatif::MultiProgress::new()).unwrap_err();
+    assert!(matches!(error, UnzipperError::InvalidPattern(_)));
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn restore_symlinks() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let zip = FileToUnzip::new(tests_dir.join("archives/links.zip"), output.join("zip"));
+    let tar = FileToUnzip::new(tests_dir.join("archives/links.tar.gz"), output.join("tar"));
+
+    let to_unzip = FilesToUnzip::from(vec![zip.clone(), tar.clone()]);
+    futures::executor::block_on(to_unzip.unzip())?;
+
+    for links in [zip.output(), tar.output()] {
+        let library = links.join("lib/libfoo.so");
+        assert!(std::fs::symlink_metadata(&library)?.is_symlink());
+        assert_eq!(std::fs::read_link(&library)?, Path::new("libfoo.so.1"));
+        assert_eq!(std::fs::read_to_string(&library)?, "foo");
+
+        let current = links.join("current");
+        assert!(std::fs::symlink_metadata(&current)?.is_symlink());
+        assert!(current.join("libfoo.so.1").is_file());
+
+        // the mode of the links must not be applied to the files they point to
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(links.join("lib/libfoo.so.1"))?
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn refuse_unsafe_symlinks() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path().join("output");
+
+    let evil = FileToUnzip::new(tests_dir.join("archives/evil-link.zip"), &output);
+    let error = unzipper::unzip_task(evil, indicatif::MultiProgress::new()).unwrap_err();
+    assert!(matches!(error, UnzipperError::UnsafeSymlink { .. }));
+    assert_eq!(error.entry(), Some("lib/passwd"));
+    assert!(std::fs::symlink_metadata(output.join("lib/passwd")).is_err());
+
+    let through = FileToUnzip::new(tests_dir.join("archives/through-link.zip"), &output);
+    let error = unzipper::unzip_task(through, indicatif::MultiProgress::new()).unwrap_err();
+    assert!(matches!(error, UnzipperError::ThroughSymlink { .. }));
+    assert_eq!(error.entry(), Some("link/file.txt"));
+    assert!(!output.join("sub/file.txt").exists());
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn refuse_long_symlinks() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path().join("output");
+
+    // the link points to a path of 5000 bytes
+    let long = FileToUnzip::new(tests_dir.join("archives/long-link.zip"), &output);
+    let error = unzipper::unzip_task(long, indicatif::MultiProgress::new()).unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::SymlinkTooLong { limit: 4096, .. }
+    ));
+    assert_eq!(error.entry(), Some("long"));
+    assert!(std::fs::symlink_metadata(output.join("long")).is_err());
+
+    // the same link declaring to point to a path of 10 bytes
+    let lying = FileToUnzip::new(tests_dir.join("archives/lying-link.zip"), &output);
+    let error = unzipper::unzip_task(lying, indicatif::MultiProgress::new()).unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::SymlinkTooLong { limit: 4096, .. }
+    ));
+    assert!(std::fs::symlink_metadata(output.join("long")).is_err());
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn limits() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let archives = tests_dir.join("archives");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let unzip = |archive: &str, limits: Limits| {
+        let file_to_unzip =
+            FileToUnzip::new(archives.join(archive), output.join(archive)).with_limits(limits);
+        unzipper::unzip_task(file_to_unzip, indicatif::MultiProgress::new())
+    };
+
+    // the entry declares to be 10 bytes large, the limit is enforced while streaming
+    let error = unzip("lying.zip", Limits::new().with_max_entry_bytes(1000)).unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::EntryTooLarge { limit: 1000, .. }
+    ));
+    assert_eq!(error.entry(), Some("zeros.bin"));
+
+    let error = unzip("zeros.zip", Limits::new().with_max_compression_ratio(100.0)).unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::CompressionRatioExceeded { .. }
+    ));
+    let error = unzip("lying.zip", Limits::new().with_max_compression_ratio(100.0)).unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::CompressionRatioExceeded { .. }
+    ));
+    // the entry declares to be stored without compression, the ratio is of the bytes read
+    let error = unzip(
+        "lying-compressed.zip",
+        Limits::new().with_max_compression_ratio(100.0),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::CompressionRatioExceeded { .. }
+    ));
+    let error = unzip(
+        "zeros.tar.gz",
+        Limits::new().with_max_compression_ratio(100.0),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::CompressionRatioExceeded { .. }
+    ));
+
+    let error = unzip("mice.zip", Limits::new().with_max_entries(2)).unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::TooManyEntries { limit: 2, .. }
+    ));
+    let error = unzip("mice.tar.zst", Limits::new().with_max_entries(2)).unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::TooManyEntries { limit: 2, .. }
+    ));
+
+    let report = unzip(
+        "zeros.tar.gz",
+        Limits::new()
+            .with_max_total_bytes(1024 * 1024)
+            .with_max_compression_ratio(1000.0),
+    )?;
+    assert_eq!(report.bytes(), 1024 * 1024);
+
+    // the limits of the batch apply to every archive without limits of its own
+    let to_unzip = FilesToUnzip::new()
+        .add_file(archives.join("mice.tar"), output.join("total"))
+        .with_limits(Limits::new().with_max_total_bytes(10));
+    let error = futures::executor::block_on(to_unzip.unzip()).unwrap_err();
+    let failure = &error.failures()[0];
+    assert!(matches!(
+        failure,
+        UnzipperError::ArchiveTooLarge { limit: 10, .. }
+    ));
+    assert_eq!(failure.entry(), Some("mice/squeak.sh"));
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn concurrency() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let archives = tests_dir.join("archives");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let names = ["cat.zip", "dog.zip", "mice.zip", "mice.tar.xz", "links.zip"];
+    let to_unzip = FilesToUnzip::from(
+        names.map(|name| FileToUnzip::new(archives.join(name), output.join(name))),
+    )
+    .with_concurrency(3);
+    let report = futures::executor::block_on(to_unzip.unzip())?;
+
+    // the reports keep the order of the archives
+    let extracted = report
+        .archives()
+        .iter()
+        .map(|archive| archive.archive().file_name().unwrap().to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(extracted, names);
+    assert!(output.join("cat.zip/cat.txt").is_file());
+    assert!(output.join("mice.tar.xz/mice/jerry.txt").is_file());
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn threads() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let archives = tests_dir.join("archives");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let many = FileToUnzip::new(archives.join("many.zip"), output.join("many")).with_threads(4);
+    let report = unzipper::unzip_task(many, indicatif::MultiProgress::new())?;
+    assert_eq!(report.outcomes().len(), 64);
+    assert_eq!(report.bytes(), 250000);
+    for folder in 0..4 {
+        for file in 0..16 {
+            let content =
+                std::fs::read_to_string(output.join(format!("many/many/{folder}/{file}.txt")))?;
+            assert_eq!(
+                content,
+                format!("{folder}/{file}\n").repeat(100 * (file + 1))
+            );
+        }
+    }
+
+    // links are created once the files are extracted
+    let links = FileToUnzip::new(archives.join("links.zip"), output.join("links")).with_threads(4);
+    unzipper::unzip_task(links, indicatif::MultiProgress::new())?;
+    assert_eq!(
+        std::fs::read_to_string(output.join("links/lib/libfoo.so"))?,
+        "foo"
+    );
+
+    let lying = FileToUnzip::new(archives.join("lying.zip"), output.join("lying"))
+        .with_threads(4)
+        .with_limits(Limits::new().with_max_entry_bytes(1000));
+    let error = unzipper::unzip_task(lying, indicatif::MultiProgress::new()).unwrap_err();
+    assert!(matches!(
+        error,
+        UnzipperError::EntryTooLarge { limit: 1000, .. }
+    ));
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn preserve_modification_times() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let zip = FileToUnzip::new(tests_dir.join("archives/mice.zip"), output.join("zip"));
+    let tar = FileToUnzip::new(tests_dir.join("archives/mice.tar.bz2"), output.join("tar"));
+    let links = FileToUnzip::new(tests_dir.join("archives/links.zip"), output.join("links"));
+
+    let to_unzip = FilesToUnzip::from(vec![zip.clone(), tar.clone(), links.clone()]);
+    futures::executor::block_on(to_unzip.unzip())?;
+
+    let modified = |path: PathBuf| -> Result<u64, Box<dyn Error>> {
+        Ok(std::fs::metadata(path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)?
+            .as_secs())
+    };
+
+    // from the extended timestamp extra field
+    assert_eq!(modified(zip.output().join("mice"))?, 1647950157);
+    assert_eq!(modified(zip.output().join("mice/jerry.txt"))?, 1647950121);
+    assert_eq!(modified(zip.output().join("mice/cherie.txt"))?, 1647950157);
+
+    // from the MS-DOS date and time 2022-03-22 11:55:00, which is in local time
+    let dos_modified = chrono::NaiveDate::from_ymd_opt(2022, 3, 22)
+        .and_then(|date| date.and_hms_opt(11, 55, 0))
+        .and_then(|local| local.and_local_timezone(chrono::Local).earliest())
+        .map(|local| local.timestamp() as u64);
+    assert_eq!(Some(modified(links.output().join("lib"))?), dos_modified);
+    assert_eq!(
+        Some(modified(links.output().join("lib/libfoo.so.1"))?),
+        dos_modified
+    );
+
+    assert_eq!(modified(tar.output().join("mice"))?, 1647950100);
+    assert_eq!(modified(tar.output().join("mice/squeak.sh"))?, 1647950100);
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn overwrite_policies() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+    let jerry = output.join("mice/jerry.txt");
+    let squeak = output.join("mice/squeak.sh");
+
+    let unzip = |archive: &str, overwrite: OverwritePolicy| {
+        let file_to_unzip = FileToUnzip::new(tests_dir.join("archives").join(archive), output)
+            .with_overwrite(overwrite);
+        unzipper::unzip_task(file_to_unzip, indicatif::MultiProgress::new())
+    };
+    let status = |report: &ArchiveReport, entry: &str| {
+        report
+            .outcomes()
+            .iter()
+            .find(|outcome| outcome.entry() == entry)
+            .map(|outcome| outcome.status())
+    };
+
+    let report = unzip("mice.zip", OverwritePolicy::Overwrite)?;
+    assert_eq!(
+        status(&report, "mice/jerry.txt"),
+        Some(EntryStatus::Created)
+    );
+    // folders are merged and not reported
+    assert_eq!(status(&report, "mice/"), None);
+
+    std::fs::write(&jerry, "local edit")?;
+    let report = unzip("mice.zip", OverwritePolicy::SkipExisting)?;
+    assert_eq!(status(&report, "mice/jerry.txt"), Some(EntryStatus::Kept));
+    assert_eq!(std::fs::read_to_string(&jerry)?, "local edit");
+
+    // the local edit is newer than the archive entry
+    let report = unzip("mice.zip", OverwritePolicy::Newer)?;
+    assert_eq!(status(&report, "mice/jerry.txt"), Some(EntryStatus::Kept));
+    filetime::set_file_mtime(&jerry, filetime::FileTime::from_unix_time(946684800, 0))?;
+    let report = unzip("mice.zip", OverwritePolicy::Newer)?;
+    assert_eq!(
+        status(&report, "mice/jerry.txt"),
+        Some(EntryStatus::Overwritten)
+    );
+    assert_eq!(std::fs::read_to_string(&jerry)?, "");
+
+    std::fs::write(&jerry, "local edit")?;
+    let error = unzip("mice.zip", OverwritePolicy::Fail).unwrap_err();
+    assert!(matches!(error, UnzipperError::Conflict { .. }));
+    assert_eq!(error.target(), Some(jerry.as_path()));
+    assert_eq!(std::fs::read_to_string(&jerry)?, "local edit");
+
+    let report = unzip("mice.zip", OverwritePolicy::Differs)?;
+    assert_eq!(
+        status(&report, "mice/jerry.txt"),
+        Some(EntryStatus::Overwritten)
+    );
+    assert_eq!(status(&report, "mice/cherie.txt"), Some(EntryStatus::Kept));
+    assert_eq!(std::fs::read_to_string(&jerry)?, "");
+
+    // tar archives have no checksums of their entries
+    let report = unzip("mice.tar.gz", OverwritePolicy::Differs)?;
+    assert_eq!(
+        status(&report, "mice/squeak.sh"),
+        Some(EntryStatus::Created)
+    );
+    std::fs::write(&squeak, "#!/bin/sh\necho meow\n")?;
+    let report = unzip("mice.tar.gz", OverwritePolicy::Differs)?;
+    assert_eq!(
+        status(&report, "mice/squeak.sh"),
+        Some(EntryStatus::Overwritten)
+    );
+    assert_eq!(status(&report, "mice/jerry.txt"), Some(EntryStatus::Kept));
+    assert_eq!(
+        std::fs::read_to_string(&squeak)?,
+        "#!/bin/sh\necho squeak\n"
+    );
+    assert!(!output.join("mice/jerry.txt.part").exists());
+
+    let report = unzip("mice.tar.gz", OverwritePolicy::Overwrite)?;
+    assert_eq!(
+        status(&report, "mice/squeak.sh"),
+        Some(EntryStatus::Overwritten)
+    );
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn atomic() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let archives = tests_dir.join("archives");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path().join("mice");
+    let siblings = || std::fs::read_dir(output_dir.path()).unwrap().count();
+
+    let unzip = |archive: &str, file_to_unzip: fn(FileToUnzip) -> FileToUnzip| {
+        let file_to_unzip = file_to_unzip(FileToUnzip::new(archives.join(archive), &output));
+        unzipper::unzip_task(file_to_unzip.atomic(true), indicatif::MultiProgress::new())
+    };
+
+    // a failed extraction leaves nothing behind
+    let error = unzip("lying.zip", |file| {
+        file.with_limits(Limits::new().with_max_entry_bytes(1000))
+    })
+    .unwrap_err();
+    assert!(matches!(error, UnzipperError::EntryTooLarge { .. }));
+    assert_eq!(siblings(), 0);
+
+    // an empty output folder is fine
+    std::fs::create_dir(&output)?;
+    let report = unzip("mice.tar.gz", |file| file)?;
+    assert_eq!(report.output(), output);
+    assert!(report
+        .outcomes()
+        .iter()
+        .all(|outcome| outcome.path().starts_with(&output)));
+    assert!(output.join("mice/squeak.sh").is_file());
+    assert_eq!(siblings(), 1);
+
+    let error = unzip("links.zip", |file| file).unwrap_err();
+    assert!(matches!(error, UnzipperError::OutputExists { .. }));
+    assert!(output.join("mice/squeak.sh").is_file());
+    assert_eq!(siblings(), 1);
+
+    unzip("links.zip", |file| file.replace_output(true))?;
+    assert!(output.join("lib/libfoo.so.1").is_file());
+    assert!(!output.join("mice").exists());
+    assert_eq!(siblings(), 1);
+
+    // the existing output is kept if the replacement fails
+    let error = unzip("mice.tar.gz", |file| {
+        file.replace_output(true)
+            .with_limits(Limits::new().with_max_entries(2))
+    })
+    .unwrap_err();
+    assert!(matches!(error, UnzipperError::TooManyEntries { .. }));
+    assert!(output.join("lib/libfoo.so.1").is_file());
+    assert_eq!(siblings(), 1);
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn read_entries() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let archives = tests_dir.join("archives");
+
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+
+    let squeak = "#!/bin/sh\necho squeak\n";
+    for (name, file, content, missing) in [
+        (
+            "links.zip",
+            "lib/libfoo.so.1",
+            "foo",
+            ["lib", "lib/libfoo.so"],
+        ),
+        (
+            "mice.tar",
+            "mice/squeak.sh",
+            squeak,
+            ["mice", "mice/tom.txt"],
+        ),
+        (
+            "mice.tar.xz",
+            "mice/squeak.sh",
+            squeak,
+            ["mice", "mice/tom.txt"],
+        ),
+        (
+            "mice-tarball",
+            "mice/squeak.sh",
+            squeak,
+            ["mice", "mice/tom.txt"],
+        ),
+    ] {
+        let mut archive = ArchiveReader::open(archives.join(name))?;
+        assert_eq!(archive.format().is_tar(), name != "links.zip");
+
+        assert_eq!(archive.read_to_string(file)?, content);
+        // tarballs are read from the start again for every entry
+        assert_eq!(archive.read(format!("./{file}"))?, content.as_bytes());
+
+        let mut entry = archive.entry(file)?;
+        assert_eq!(entry.name().trim_start_matches("./"), file);
+        let mut read = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut read)?;
+        assert_eq!(read, content);
+        drop(entry);
+
+        let target = output.join(name).join("extracted");
+        assert_eq!(archive.extract_to(file, &target)?, content.len() as u64);
+        assert_eq!(std::fs::read_to_string(&target)?, content);
+
+        // folders and links are not files
+        for missing in missing
+            .into_iter()
+            .chain([&*format!("../{file}"), &*format!("/{file}")])
+        {
+            let error = archive.read(missing).unwrap_err();
+            assert!(matches!(error, UnzipperError::EntryNotFound { .. }));
+            assert_eq!(error.entry(), Some(missing));
+        }
+    }
+
+    // entries that would escape the output folder can not be read either
+    let mut escape = ArchiveReader::open(archives.join("escape.tar.gz"))?;
+    let error = escape.read("../escape.txt").unwrap_err();
+    assert!(matches!(error, UnzipperError::EntryNotFound { .. }));
+    assert_eq!(escape.read_to_string("safe.txt")?.len(), 4);
+
+    let error = ArchiveReader::open(tests_dir.join("mod.rs")).err().unwrap();
+    assert!(matches!(error, UnzipperError::UnsupportedFormat(_)));
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn list_entries() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let archives = tests_dir.join("archives");
+
+    for name in ["mice.tar", "mice.tar.gz", "mice.tar.bz2", "mice.tar.zst"] {
+        let entries = unzipper::list(archives.join(name))?;
+        let names = entries
+            .iter()
+            .map(|entry| entry.name().trim_start_matches("./"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "mice/",
+                "mice/jerry.txt",
+                "mice/cherie.txt",
+                "mice/squeak.sh"
+            ]
+        );
+
+        assert_eq!(entries[0].kind(), EntryKind::Directory);
+        let squeak = &entries[3];
+        assert_eq!(squeak.kind(), EntryKind::File);
+        assert_eq!(squeak.size(), 22);
+        assert_eq!(squeak.compressed_size(), None);
+        assert_eq!(squeak.crc32(), None);
+        assert_eq!(squeak.unix_mode().map(|mode| mode & 0o777), Some(0o755));
+        assert_eq!(
+            squeak.modified(),
+            Some(UNIX_EPOCH + std::time::Duration::from_secs(1647950100))
+        );
+    }
+
+    let entries = ArchiveReader::open(archives.join("links.zip"))?.entries()?;
+    let kinds = entries
+        .iter()
+        .map(|entry| (entry.name(), entry.kind()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            ("lib/", EntryKind::Directory),
+            ("lib/libfoo.so.1", EntryKind::File),
+            ("lib/libfoo.so", EntryKind::Symlink),
+            ("current", EntryKind::Symlink),
+        ]
+    );
+
+    let library = &entries[1];
+    assert_eq!(library.size(), 3);
+    assert!(library.compressed_size().is_some());
+    assert_eq!(library.crc32(), Some(crc32fast::hash(b"foo")));
+    assert_eq!(library.unix_mode(), Some(0o100755));
+    assert!(library.modified().is_some());
+
+    let error = unzipper::list(tests_dir.join("mod.rs")).unwrap_err();
+    assert!(matches!(error, UnzipperError::UnsupportedFormat(_)));
+
+    Ok(())
+}
+
+#[test]
+fn test_integrity() -> Result<(), Box<dyn Error>> {
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let archives = tests_dir.join("archives");
+
+    let problems = |name: &str| -> Result<Vec<(Option<String>, Problem)>, UnzipperError> {
+        let report = unzipper::test(archives.join(name))?;
+        Ok(report
+            .problems()
+            .iter()
+            .map(|problem| {
+                (
+                    problem.entry().map(str::to_string),
+                    problem.problem().clone(),
+                )
+            })
+            .collect())
+    };
+
+    for name in [
+        "mice.zip",
+        "mice.tar.gz",
+        "mice.tar.zst",
+        "links.zip",
+        "many.zip",
+    ] {
+        assert_eq!(problems(name)?, []);
+    }
+    let report = ArchiveReader::open(archives.join("many.zip"))?.test()?;
+    assert!(report.is_ok());
+    assert_eq!(report.entries(), 68);
+    assert_eq!(report.bytes(), 250000);
+
+    assert_eq!(
+        problems("bad-crc.zip")?,
+        [(
+            Some("mice/squeak.txt".to_string()),
+            Problem::CrcMismatch {
+                expected: crc32fast::hash(b"squeak"),
+                actual: crc32fast::hash(b"squeal"),
+            }
+        )]
+    );
+    assert_eq!(
+        problems("lying.zip")?,
+        [(
+            Some("zeros.bin".to_string()),
+            Problem::SizeMismatch {
+                expected: 10,
+                actual: 1024 * 1024,
+            }
+        )]
+    );
+    // the end of the archive is missing as well
+    let truncated = problems("truncated.tar")?;
+    assert_eq!(
+        truncated[0],
+        (
+            Some("mice/squeak.sh".to_string()),
+            Problem::SizeMismatch {
+                expected: 22,
+                actual: 5,
+            }
+        )
+    );
+    assert!(matches!(truncated[1], (None, Problem::Unreadable { .. })));
+
+    assert_eq!(
+        problems("escape.tar.gz")?,
+        [
+            (Some("../escape.txt".to_string()), Problem::UnsafePath),
+            (Some("/absolute.txt".to_string()), Problem::UnsafePath),
+        ]
+    );
+    assert_eq!(
+        problems("evil-link.zip")?,
+        [(
+            Some("lib/passwd".to_string()),
+            Problem::UnsafeSymlink {
+                link: PathBuf::from("../../passwd")
+            }
+        )]
+    );
+
+    let error = unzipper::test(tests_dir.join("mod.rs")).unwrap_err();
+    assert!(matches!(error, UnzipperError::UnsupportedFormat(_)));
+
+    Ok(())
+}
+
+#[test]
+fn unsupported_format() -> Result<(), Box<dyn Error>> {
+    let output_dir = tempdir()?;
+    let archive = output_dir.path().join("archive.rar");
+    std::fs::write(&archive, "Rar!")?;
+
+    let error = FileToUnzip::new(&archive, output_dir.path())
+        .format()
+        .unwrap_err();
+    assert!(matches!(error, UnzipperError::UnsupportedFormat(_)));
+    assert_eq!(error.archive(), Some(archive.as_path()));
+
+    output_dir.close()?;
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() -> Result<(), Box<dyn Error>> {
+    let limits = Limits::new()
+        .with_max_total_bytes(1024)
+        .with_max_entries(10)
+        .with_max_compression_ratio(50.0);
+    let to_unzip = FilesToUnzip::from(vec![
+        FileToUnzip::new("archives/cat.zip", "output/cat")
+            .strip_components(2)
+            .include("**/*.txt")
+            .exclude("secret/**")
+            .with_limits(limits)
+            .with_overwrite(OverwritePolicy::SkipExisting)
+            .atomic(true),
+        FileToUnzip::new("archives/dog.zip", "output/dog"),
+    ]);
+
+    let json = serde_json::to_string(&to_unzip)?;
+    let deserialized: FilesToUnzip = serde_json::from_str(&json)?;
+
+    let value = serde_json::to_value(&deserialized)?;
+    let cat = &value["files"][0];
+    assert_eq!(cat["strip_components"], 2);
+    assert_eq!(cat["include"], serde_json::json!(["**/*.txt"]));
+    assert_eq!(cat["exclude"], serde_json::json!(["secret/**"]));
+    assert_eq!(
+        serde_json::from_value::<Limits>(cat["limits"].clone())?,
+        limits
+    );
+    assert_eq!(cat["overwrite"], "skip_existing");
+    assert_eq!(cat["atomic"], true);
+
+    assert_eq!(serde_json::to_string(&deserialized)?, json);
+    Ok(())
+}
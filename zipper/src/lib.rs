@@ -1,4 +1,6 @@
 mod error;
+#[cfg(all(feature = "serde", feature = "file-matcher"))]
+mod one_entry;
 
 pub use crate::error::{Result, ZipperError};
 
@@ -12,18 +14,23 @@ use zip::ZipWriter;
 
 #[cfg(feature = "file-matcher")]
 use file_matcher::OneEntry;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ToZip {
     archive: PathBuf,
     what: Vec<WhatToZip>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum WhatToZip {
     File(PathBuf),
     Folder(PathBuf),
     #[cfg(feature = "file-matcher")]
+    #[cfg_attr(feature = "serde", serde(with = "one_entry"))]
     OneEntry(OneEntry),
 }
 
@@ -76,6 +83,10 @@ impl ToZip {
         self.archive.as_path()
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "zip", skip_all, fields(archive = %self.archive.display()))
+    )]
     pub fn zip(&self) -> Result<PathBuf> {
         let archive = File::create(self.archive()).unwrap();
         let mut zip = ZipWriter::new(archive);
@@ -83,39 +94,60 @@ impl ToZip {
         let zip_options =
             SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
 
+        #[cfg(feature = "tracing")]
+        let mut entries = 0;
         for what in self.what.iter() {
-            match what {
-                WhatToZip::File(file) => {
-                    zip_file(&mut zip, file, zip_options)?;
-                }
-                WhatToZip::Folder(folder) => {
-                    zip_folder(&mut zip, folder, zip_options)?;
-                }
-                #[cfg(feature = "file-matcher")]
-                WhatToZip::OneEntry(one_entry) => {
-                    let path = one_entry.as_path_buf()?;
-                    if path.is_file() {
-                        zip_file(&mut zip, path, zip_options)?;
-                    } else if path.is_dir() {
-                        zip_folder(&mut zip, path, zip_options)?;
-                    } else {
-                        Err(ZipperError::UnknownEntryType(path))?
-                    }
-                }
+            #[cfg(feature = "tracing")]
+            {
+                entries += zip_what(&mut zip, what, zip_options)?;
             }
+            #[cfg(not(feature = "tracing"))]
+            zip_what(&mut zip, what, zip_options)?;
         }
 
         zip.finish()?;
 
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            entries,
+            bytes = std::fs::metadata(self.archive())
+                .map(|metadata| metadata.len())
+                .ok(),
+            "Created the archive"
+        );
+
         Ok(self.archive.clone())
     }
 }
 
+/// Returns the amount of added entries
+fn zip_what<F: Write + std::io::Seek, T: FileOptionExtension + Clone>(
+    zip: &mut ZipWriter<F>,
+    what: &WhatToZip,
+    zip_options: FileOptions<T>,
+) -> Result<usize> {
+    match what {
+        WhatToZip::File(file) => zip_file(zip, file, zip_options),
+        WhatToZip::Folder(folder) => zip_folder(zip, folder, zip_options),
+        #[cfg(feature = "file-matcher")]
+        WhatToZip::OneEntry(one_entry) => {
+            let path = one_entry.as_path_buf()?;
+            if path.is_file() {
+                zip_file(zip, path, zip_options)
+            } else if path.is_dir() {
+                zip_folder(zip, path, zip_options)
+            } else {
+                Err(ZipperError::UnknownEntryType(path))
+            }
+        }
+    }
+}
+
 fn zip_folder<F: Write + std::io::Seek, T: FileOptionExtension + Clone>(
     zip: &mut ZipWriter<F>,
     src_dir: impl AsRef<Path>,
     zip_options: FileOptions<T>,
-) -> Result<()> {
+) -> Result<usize> {
     let src_dir = src_dir.as_ref();
     if !src_dir.exists() {
         return Err(ZipperError::FolderDoesNotExist(src_dir.to_owned()));
@@ -125,6 +157,7 @@ fn zip_folder<F: Write + std::io::Seek, T: FileOptionExtension + Clone>(
     let it = walk_dir.into_iter();
 
     let mut buffer = Vec::new();
+    let mut entries = 0;
     for entry in it {
         let entry = entry?;
         let path = entry.path();
@@ -161,14 +194,20 @@ fn zip_folder<F: Write + std::io::Seek, T: FileOptionExtension + Clone>(
             let mut f = File::open(path)?;
 
             f.read_to_end(&mut buffer)?;
-            zip.write_all(&*buffer)?;
+            zip.write_all(&buffer)?;
+
+            #[cfg(feature = "tracing")]
+            tracing::trace!(entry = %path.display(), bytes = buffer.len(), "Added a file");
+
             buffer.clear();
-        } else if name.len() != 0 {
+            entries += 1;
+        } else if !name.is_empty() {
             zip.add_directory(name, zip_options.clone())?;
+            entries += 1;
         }
     }
 
-    Ok(())
+    Ok(entries)
 }
 
 #[allow(unused_mut)]
@@ -176,7 +215,7 @@ fn zip_file<F: Write + std::io::Seek, T: FileOptionExtension>(
     zip: &mut ZipWriter<F>,
     file: impl AsRef<Path>,
     mut zip_options: FileOptions<T>,
-) -> Result<()> {
+) -> Result<usize> {
     let file = file.as_ref();
 
     if !file.exists() {
@@ -205,7 +244,11 @@ fn zip_file<F: Write + std::io::Seek, T: FileOptionExtension>(
 
     f.read_to_end(&mut buffer)?;
     zip.write_all(buffer.as_slice())?;
+
+    #[cfg(feature = "tracing")]
+    tracing::trace!(entry = name, bytes = buffer.len(), "Added a file");
+
     buffer.clear();
 
-    Ok(())
+    Ok(1)
 }
@@ -0,0 +1,55 @@
+use file_matcher::{EntryName, EntryType, FileNamed, FileOrFolderNamed, FolderNamed, OneEntry};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::PathBuf;
+
+/// (De)serializes a matched entry as its name description, type and directory.
+/// The name alias is not kept, it does not affect what is zipped
+#[derive(Serialize, Deserialize)]
+struct OneEntryForm {
+    entry_name: EntryName,
+    entry_type: EntryType,
+    directory: PathBuf,
+}
+
+pub(crate) fn serialize<S: Serializer>(
+    one_entry: &OneEntry,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    OneEntryForm {
+        entry_name: one_entry.entry_name().clone(),
+        entry_type: one_entry.entry_type().clone(),
+        directory: one_entry.directory().to_path_buf(),
+    }
+    .serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<OneEntry, D::Error> {
+    let form = OneEntryForm::deserialize(deserializer)?;
+    Ok(match form.entry_type {
+        EntryType::File => file_named(form.entry_name).within(form.directory),
+        EntryType::Folder => folder_named(form.entry_name).within(form.directory),
+        EntryType::Any => file_or_folder_named(form.entry_name).within(form.directory),
+    })
+}
+
+macro_rules! named {
+    ($function:ident, $named:ident) => {
+        fn $function(entry_name: EntryName) -> $named {
+            match entry_name {
+                EntryName::Exact(name) => $named::exact(name),
+                EntryName::Any(names) => $named::any(names),
+                EntryName::AnyNamed(names) => {
+                    $named::any_named(names.into_iter().map($function).collect())
+                }
+                EntryName::Regex(pattern) => $named::regex(pattern),
+                EntryName::Wildmatch(pattern) => $named::wildmatch(pattern),
+            }
+        }
+    };
+}
+
+named!(file_named, FileNamed);
+named!(folder_named, FolderNamed);
+named!(file_or_folder_named, FileOrFolderNamed);
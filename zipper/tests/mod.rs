@@ -0,0 +1,31 @@
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    use zipper::ToZip;
+
+    let to_zip = ToZip::new("archive.zip").file("README.md").folder("src");
+
+    let json = serde_json::to_string(&to_zip)?;
+    let deserialized: ToZip = serde_json::from_str(&json)?;
+
+    assert_eq!(serde_json::to_string(&deserialized)?, json);
+    Ok(())
+}
+
+#[cfg(all(feature = "serde", feature = "file-matcher"))]
+#[test]
+fn serde_round_trip_one_entry() -> Result<(), Box<dyn std::error::Error>> {
+    use file_matcher::{FileNamed, FolderNamed};
+    use zipper::ToZip;
+
+    let to_zip = ToZip::new("archive.zip")
+        .one_entry(FileNamed::wildmatch("*.md").within("."))
+        .one_entry(FolderNamed::any_named(vec![FolderNamed::exact("src")]).within("."));
+
+    let json = serde_json::to_string(&to_zip)?;
+    assert!(json.contains("*.md"));
+    let deserialized: ToZip = serde_json::from_str(&json)?;
+
+    assert_eq!(serde_json::to_string(&deserialized)?, json);
+    Ok(())
+}